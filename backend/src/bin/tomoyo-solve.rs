@@ -0,0 +1,196 @@
+use serde_json::{json, Value};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use project_tomoyo::{
+    cost::{ConstraintRegistry, Weights},
+    events::LogEvents,
+    files,
+    logic::{AnnealingParams, Shift, Timetable, TimetableData},
+};
+
+const MAX_PERIODS_PER_DAY: u32 = 7;
+
+const USAGE: &str = "Usage: tomoyo-solve <import dir> [--timetable <file>] [--params <file>] [--seconds <n>] [--iterations <n>] [--out <dir>]
+
+Runs the annealer without the frontend. The params file holds the same fields as the play message:
+  alpha, t0, sa_max, static_classes, acceptance, seed, hard_1..soft_2, weights, constraints
+  stopping criteria: max_seconds, max_iterations, min_temperature, stop_at_zero_hard, stagnation_steps
+  escaping local optima: escape (none, reheat, restart), escape_after, reheat_fraction, restart_kicks
+  parallel chains: chains, exchange_steps, chain_temperature_ratio
+  tabu search: algorithm (annealing, tabu), tabu_neighbors, tabu_tenure
+alpha, t0 and sa_max default to the frontend's values. --seconds and --iterations override
+max_seconds and max_iterations, one of the criteria other than stop_at_zero_hard is needed
+(min_temperature doesn't end a tabu search).
+The best solution is written to <out>/timetable.json and its cost to <out>/report.json.";
+
+struct Args {
+    import_dir: PathBuf,
+    timetable: Option<PathBuf>,
+    params: Option<PathBuf>,
+    seconds: Option<u64>,
+    iterations: Option<u64>,
+    out: PathBuf,
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn import_error(file: &Path, message: &str) -> ! {
+    eprintln!("Failed to import {}: {}", file.display(), message);
+    process::exit(1);
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        import_dir: PathBuf::new(),
+        timetable: None,
+        params: None,
+        seconds: None,
+        iterations: None,
+        out: PathBuf::from("./export"),
+    };
+    let mut import_dir: Option<PathBuf> = None;
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .unwrap_or_else(|| usage_error(&format!("Missing value for {}", arg)))
+        };
+        let number = |v: String| {
+            v.parse::<u64>()
+                .unwrap_or_else(|_| usage_error(&format!("Invalid number: {}", v)))
+        };
+
+        match arg.as_str() {
+            "--timetable" => args.timetable = Some(PathBuf::from(value())),
+            "--params" => args.params = Some(PathBuf::from(value())),
+            "--seconds" => args.seconds = Some(number(value())),
+            "--iterations" => args.iterations = Some(number(value())),
+            "--out" => args.out = PathBuf::from(value()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("--") => usage_error(&format!("Unknown option: {}", arg)),
+            _ if import_dir.is_none() => import_dir = Some(PathBuf::from(arg)),
+            _ => usage_error(&format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    args.import_dir = import_dir.unwrap_or_else(|| usage_error("Missing import dir"));
+
+    args
+}
+
+/// Play data from the params file and the budget options, with the fields the frontend always sends filled in
+fn play_data(args: &Args) -> Value {
+    let mut data = json!({
+        "alpha": 0.97,
+        "t0": 1.0,
+        "sa_max": 10000,
+        "static_classes": "",
+    });
+
+    if let Some(path) = &args.params {
+        let contents = fs::read_to_string(path).unwrap();
+        let params: Value = serde_json::from_str(&contents).unwrap();
+
+        for (key, value) in params.as_object().expect("Params must be a JSON object.") {
+            data[key] = value.clone();
+        }
+    }
+
+    if let Some(seconds) = args.seconds {
+        data["max_seconds"] = json!(seconds);
+    }
+    if let Some(iterations) = args.iterations {
+        data["max_iterations"] = json!(iterations);
+    }
+
+    data
+}
+
+fn load_timetable(args: &Args) -> Timetable {
+    let mut timetable = Timetable::new(
+        TimetableData::default(),
+        MAX_PERIODS_PER_DAY,
+        vec![],
+        vec![],
+    );
+
+    for tab in files::TABS {
+        if !args.import_dir.join(format!("{}.csv", tab)).exists() {
+            println!("No {}.csv, skipping", tab);
+            continue;
+        }
+
+        if let Err(message) = files::import_tab(&mut timetable, &args.import_dir, tab) {
+            import_error(&args.import_dir.join(format!("{}.csv", tab)), &message);
+        }
+    }
+
+    // sizes both tables, so they're filled even without a starting timetable
+    timetable.generate_random_table(Shift::First);
+    timetable.generate_random_table(Shift::Second);
+
+    if let Some(path) = &args.timetable {
+        if let Err(message) = files::import_timetable(&mut timetable, path) {
+            import_error(path, &message);
+        }
+    }
+
+    timetable
+}
+
+fn main() {
+    let args = parse_args();
+
+    let mut timetable = load_timetable(&args);
+
+    let data = play_data(&args);
+    timetable.constraints = Arc::new(ConstraintRegistry::from_json(&data));
+    let weights = Weights::from_json(&data, &timetable.constraints);
+    let params = AnnealingParams::from_json(&data);
+    if !params.stop.bounded(params.algorithm) {
+        usage_error("Missing a stopping criterion, the run would never end");
+    }
+
+    // nothing pauses a headless run, only the stopping criteria end it
+    let running = AtomicBool::new(true);
+    let result = timetable.start_algorithm(&running, &params, &weights, &mut LogEvents);
+
+    println!("Duration: {:.3}s", result.elapsed_secs);
+
+    let report = json!({
+        "reason": result.reason,
+        "seed": result.seed,
+        "duration_secs": result.elapsed_secs,
+        "iterations": result.iterations,
+        "escapes": result.escapes,
+        "current": result.current,
+        "current_total": result.current.total(),
+        "best": result.best,
+        "best_total": result.best.total(),
+        "report": timetable.detailed_cost(&weights),
+    });
+
+    fs::create_dir_all(&args.out).unwrap();
+    write_json(
+        &args.out.join("timetable.json"),
+        &files::timetable_json(&timetable),
+    );
+    write_json(&args.out.join("report.json"), &report);
+}
+
+fn write_json(path: &Path, json: &Value) {
+    fs::write(path, json.to_string()).unwrap();
+    println!("Wrote {}", path.display());
+}
@@ -1,142 +1,1037 @@
-use std::{collections::HashMap, print, println, vec};
+use std::{cell::OnceCell, collections::HashMap, fmt, vec};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::{
-    logic::{ClassSlots, Shift, Slot, SlotData, Timetable, TimetableData},
-    util::{TeacherSlot, TeacherSlots},
+    logic::{ClassSlots, Move, Shift, Slot, SlotData, Timetable, TimetableData},
+    util::{self, TeacherSlot, TeacherSlots},
+    DayAvailability, Relation, SubjectDaysKind, SubjectTime,
 };
 
-/* ==================== */
-/*   HARD CONSTRAINTS   */
-/* ==================== */
+/// A single place in the timetable where a constraint is broken, fields that don't apply are left empty
+///
+/// The severities of every violation of a constraint add up to its cost
+#[derive(Clone, Serialize, Debug, Default)]
+pub struct Violation {
+    pub constraint: String, // id of the constraint, filled in by `constraint_report`
+    pub shift: Option<i32>,
+    pub class: Option<usize>,
+    pub teacher: Option<usize>,
+    pub slot: Option<u32>, // index into a class/teacher timetable
+    pub day: Option<u32>,
+    pub room: Option<usize>,
+    pub severity: i32,
+}
 
-pub fn repeating_rooms(timetable: &Timetable, shift: Shift, debug: bool) -> i32 {
-    let mut points = 0;
+impl Violation {
+    /// Violation at the given index of a class/teacher timetable
+    fn at(max_periods_per_day: u32, index: u32) -> Self {
+        Self {
+            slot: Some(index),
+            day: Some(index / max_periods_per_day),
+            ..Default::default()
+        }
+    }
 
-    for period in 0..(timetable.max_periods_per_day * 5) {
-        let mut seen_rooms: Vec<usize> = vec![];
+    /// Violation spanning an entire day
+    fn on(day: u32) -> Self {
+        Self {
+            day: Some(day),
+            ..Default::default()
+        }
+    }
+}
 
-        for class in 0..timetable.table(shift).len() {
-            match timetable.table(shift)[class].slots[period as usize] {
-                Slot::Single(s) => match s {
-                    SlotData::PartiallyFilled { room, .. } => match room {
-                        Some(r) => {
-                            if seen_rooms.contains(&r) {
-                                points += 1;
-
-                                if debug {
-                                    println!("  {}", timetable.data.rooms[r].name);
-                                }
-                            } else {
-                                seen_rooms.push(r);
-                            }
-                        }
-                        None => {}
-                    },
+/// Adds a violation worth `severity` points to the list, if the caller asked for one
+fn record(violations: &mut Option<&mut Vec<Violation>>, severity: i32, violation: Violation) {
+    if let Some(v) = violations.as_mut() {
+        v.push(Violation {
+            severity,
+            ..violation
+        });
+    }
+}
 
-                    _ => {}
-                },
-                Slot::Double { first, second, .. } => {
-                    match first {
-                        SlotData::PartiallyFilled { room, .. } => match room {
-                            Some(r) => {
-                                if seen_rooms.contains(&r) {
-                                    points += 1;
-
-                                    if debug {
-                                        println!("  {}", timetable.data.rooms[r].name);
-                                    }
-                                } else {
-                                    seen_rooms.push(r);
-                                }
-                            }
-                            None => {}
-                        },
+/// Cost of a single constraint along with everywhere it's broken
+#[derive(Clone, Serialize, Debug)]
+pub struct ConstraintReport {
+    pub id: String,
+    pub name: String,
+    pub explanation: String,
+    pub hard: bool,
+    pub enabled: bool, // disabled constraints are still reported, but don't count towards the total
+    pub cost: i32,     // already multiplied
+    pub violations: Vec<Violation>,
+}
 
-                        _ => {}
-                    }
+#[derive(Clone, Serialize, Debug)]
+pub struct ShiftReport {
+    pub shift: i32,
+    pub hard: i32,
+    pub soft: i32,
+    pub constraints: Vec<ConstraintReport>,
+}
 
-                    match second {
-                        SlotData::PartiallyFilled { room, .. } => match room {
-                            Some(r) => {
-                                if seen_rooms.contains(&r) {
-                                    points += 1;
-
-                                    if debug {
-                                        println!("  {}", timetable.data.rooms[r].name);
-                                    }
-                                } else {
-                                    seen_rooms.push(r);
-                                }
-                            }
-                            None => {}
-                        },
+#[derive(Clone, Serialize, Debug)]
+pub struct CostReport {
+    pub shifts: Vec<ShiftReport>,
+    pub teacher_shifts: ConstraintReport,
+}
 
-                        _ => {}
-                    }
+/// Runs a constraint while collecting its violations, every constraint function takes them as its last argument
+pub fn constraint_report(
+    id: &str,
+    name: &str,
+    hard: bool,
+    shift: Option<Shift>,
+    multiplier: i32,
+    evaluate: impl FnOnce(Option<&mut Vec<Violation>>) -> i32,
+) -> ConstraintReport {
+    let mut violations: Vec<Violation> = vec![];
+    let cost = multiplier * evaluate(Some(&mut violations));
+
+    for violation in violations.iter_mut() {
+        violation.constraint = id.to_string();
+        violation.shift = shift.map(|s| s.to_i32());
+        violation.severity *= multiplier;
+    }
+
+    ConstraintReport {
+        id: id.to_string(),
+        name: name.to_string(),
+        explanation: String::new(),
+        hard,
+        enabled: true,
+        cost,
+        violations,
+    }
+}
+
+/// A single shift of a timetable as seen by the constraints, the teacher table is only built if a constraint asks for it
+pub struct ShiftView<'a> {
+    pub timetable: &'a Timetable,
+    pub shift: Shift,
+    teacher_table: OnceCell<Vec<TeacherSlots>>,
+}
+
+impl<'a> ShiftView<'a> {
+    pub fn new(timetable: &'a Timetable, shift: Shift) -> Self {
+        Self {
+            timetable,
+            shift,
+            teacher_table: OnceCell::new(),
+        }
+    }
+
+    pub fn teacher_table(&self) -> &Vec<TeacherSlots> {
+        self.teacher_table.get_or_init(|| {
+            util::class_table_to_teacher_table(
+                self.timetable.table(self.shift),
+                &self.timetable.data,
+                self.timetable.max_periods_per_day,
+            )
+        })
+    }
+}
+
+pub trait Constraint: Send + Sync {
+    /// Stable identifier used in the `play` message and in violations
+    fn id(&self) -> &str;
+
+    /// Human readable name shown in reports
+    fn name(&self) -> &str;
+
+    fn hard(&self) -> bool;
+
+    /// Weight used unless the `weights` object of the `play` message names the constraint, when empty the hard/soft multiplier of the shift is used instead
+    fn weight(&self) -> Option<i32> {
+        None
+    }
+
+    fn evaluate(&self, view: &ShiftView, violations: Option<&mut Vec<Violation>>) -> i32;
+
+    /// How the cost splits into parts, everything is re-evaluated after every move unless overridden
+    fn scope(&self) -> Scope {
+        Scope::Whole
+    }
+
+    /// Cost of a single part of the constraint's scope, the costs of every part add up to `evaluate`
+    fn evaluate_part(
+        &self,
+        view: &ShiftView,
+        _part: usize,
+        violations: Option<&mut Vec<Violation>>,
+    ) -> i32 {
+        self.evaluate(view, violations)
+    }
+
+    /// What the constraint checks for, shown along with its violations
+    fn explain(&self) -> &str;
+}
+
+/// Parts a constraint's cost splits into, which can each be evaluated on their own
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Scope {
+    Whole,
+    Class,    // a single class timetable
+    ClassDay, // a single day of a class timetable, indexed by class * 5 + day
+    Slot,     // the same slot in every class timetable
+    Teacher,  // every lesson of a single teacher
+}
+
+impl Scope {
+    pub fn part_count(&self, view: &ShiftView) -> usize {
+        let timetable = view.timetable;
+
+        match self {
+            Scope::Whole => 1,
+            Scope::Class => timetable.table(view.shift).len(),
+            Scope::ClassDay => timetable.table(view.shift).len() * 5,
+            Scope::Slot => (timetable.max_periods_per_day * 5) as usize,
+            Scope::Teacher => timetable.data.teachers.len(),
+        }
+    }
+
+    /// Parts whose cost could have been changed by the move
+    pub fn touched(&self, view: &ShiftView, mv: &Move) -> Vec<usize> {
+        match self {
+            Scope::Whole => vec![0],
+            Scope::Class => vec![mv.class],
+            Scope::ClassDay => mv
+                .days(view.timetable.max_periods_per_day)
+                .iter()
+                .map(|day| mv.class * 5 + *day as usize)
+                .collect(),
+            Scope::Slot => mv.changes.iter().map(|c| c.0).collect(),
+            Scope::Teacher => mv.teachers(),
+        }
+    }
+}
+
+/// Class timetable and day of a `Scope::ClassDay` part
+fn class_day<'a>(view: &ShiftView<'a>, part: usize) -> (&'a ClassSlots, u32) {
+    (
+        &view.timetable.table(view.shift)[part / 5],
+        (part % 5) as u32,
+    )
+}
+
+/// Timetable of the teacher of a `Scope::Teacher` part
+fn teacher_part(view: &ShiftView, part: usize) -> TeacherSlots {
+    util::teacher_slots(
+        view.timetable.table(view.shift),
+        part,
+        view.timetable.max_periods_per_day,
+    )
+}
+
+/// Constraint backed by one of the functions in this file
+struct FnConstraint {
+    id: &'static str,
+    name: &'static str,
+    hard: bool,
+    explanation: &'static str,
+    scope: Scope,
+    evaluate: fn(&ShiftView, Option<&mut Vec<Violation>>) -> i32,
+    evaluate_part: fn(&ShiftView, usize, Option<&mut Vec<Violation>>) -> i32,
+}
+
+impl Constraint for FnConstraint {
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn hard(&self) -> bool {
+        self.hard
+    }
+
+    fn evaluate(&self, view: &ShiftView, violations: Option<&mut Vec<Violation>>) -> i32 {
+        (self.evaluate)(view, violations)
+    }
+
+    fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    fn evaluate_part(
+        &self,
+        view: &ShiftView,
+        part: usize,
+        violations: Option<&mut Vec<Violation>>,
+    ) -> i32 {
+        (self.evaluate_part)(view, part, violations)
+    }
+
+    fn explain(&self) -> &str {
+        self.explanation
+    }
+}
+
+/// Overrides for a single constraint, sent in the `constraints` object of the `play` message
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct ConstraintConfig {
+    pub enabled: Option<bool>,
+}
+
+pub struct ConstraintEntry {
+    pub constraint: Box<dyn Constraint>,
+    pub enabled: bool,
+}
+
+/// How much each constraint is worth, sent in the `weights` object of the `play` message and keyed by constraint id
+///
+/// Constraints that aren't named fall back to their own weight, then to the hard/soft multiplier of their shift
+#[derive(Clone, Debug)]
+pub struct Weights {
+    pub hard_1: i32,
+    pub soft_1: i32,
+    pub hard_2: i32,
+    pub soft_2: i32,
+    pub constraints: HashMap<String, i32>,
+}
+
+impl Weights {
+    pub fn new(hard_1: i32, soft_1: i32, hard_2: i32, soft_2: i32) -> Self {
+        Self {
+            hard_1,
+            soft_1,
+            hard_2,
+            soft_2,
+            constraints: HashMap::new(),
+        }
+    }
+
+    /// Weights from the `weights` object of play data, the hard/soft multipliers of each shift are used for everything it doesn't name
+    pub fn from_json(data: &Value, registry: &ConstraintRegistry) -> Self {
+        let multiplier = |key: &str| data[key].as_i64().unwrap_or(1) as i32;
+
+        let mut weights = Weights::new(
+            multiplier("hard_1"),
+            multiplier("soft_1"),
+            multiplier("hard_2"),
+            multiplier("soft_2"),
+        );
+
+        let config = &data["weights"];
+        if !config.is_null() {
+            let config: HashMap<String, i32> = serde_json::from_value(config.clone()).unwrap();
+
+            for (id, weight) in config {
+                if id != "teacher_shifts" && !registry.contains(&id) {
+                    println!("Unknown constraint: {}", id);
+                    continue;
                 }
+
+                weights.constraints.insert(id, weight);
             }
         }
+
+        weights
     }
 
-    points
+    pub fn of(&self, constraint: &dyn Constraint, shift: Shift) -> i32 {
+        if let Some(weight) = self.constraints.get(constraint.id()) {
+            return *weight;
+        }
+
+        if let Some(weight) = constraint.weight() {
+            return weight;
+        }
+
+        match (shift, constraint.hard()) {
+            (Shift::First, true) => self.hard_1,
+            (Shift::First, false) => self.soft_1,
+            (Shift::Second, true) => self.hard_2,
+            (Shift::Second, false) => self.soft_2,
+        }
+    }
+
+    /// Weight of the cost of teachers teaching in both shifts during a day, which isn't tied to either shift
+    pub fn teacher_shifts(&self) -> i32 {
+        self.constraints.get("teacher_shifts").copied().unwrap_or(1)
+    }
 }
 
-/// Increment points by 1 for each teacher teaching multiple classes in the same period
-pub fn hard_repeating_teachers(timetable: &Timetable, shift: Shift, debug: bool) -> i32 {
-    let mut points = 0;
+/// Every constraint the timetable is scored against, `hard_points`, `soft_points` and `detailed_cost` all go through it
+pub struct ConstraintRegistry {
+    pub entries: Vec<ConstraintEntry>,
+}
 
-    for period in 0..(timetable.max_periods_per_day * 5) {
-        let mut seen_teachers: Vec<usize> = vec![];
+impl ConstraintRegistry {
+    pub fn empty() -> Self {
+        Self { entries: vec![] }
+    }
 
-        for class in 0..timetable.table(shift).len() {
-            match timetable.table(shift)[class].slots[period as usize] {
-                Slot::Single(s) => match s {
-                    SlotData::PartiallyFilled { teacher, .. } => {
-                        if seen_teachers.contains(&teacher) {
-                            points += 1;
-
-                            if debug {
-                                println!("  {}", timetable.data.teachers[teacher].name);
-                            }
-                        } else {
-                            seen_teachers.push(teacher);
-                        }
+    pub fn register(&mut self, constraint: Box<dyn Constraint>, enabled: bool) {
+        self.entries.push(ConstraintEntry {
+            constraint,
+            enabled,
+        });
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.entries.iter().any(|e| e.constraint.id() == id)
+    }
+
+    /// Default constraints with the overrides from the `constraints` object of play data applied
+    pub fn from_json(data: &Value) -> Self {
+        let mut registry = ConstraintRegistry::default();
+
+        let config = &data["constraints"];
+        if !config.is_null() {
+            let config: HashMap<String, ConstraintConfig> =
+                serde_json::from_value(config.clone()).unwrap();
+            registry.configure(&config);
+        }
+
+        registry
+    }
+
+    /// Applies the overrides from the `play` message, unknown constraints are skipped
+    pub fn configure(&mut self, config: &HashMap<String, ConstraintConfig>) {
+        for (id, c) in config.iter() {
+            match self.entries.iter_mut().find(|e| e.constraint.id() == id) {
+                Some(entry) => {
+                    if let Some(enabled) = c.enabled {
+                        entry.enabled = enabled;
                     }
+                }
+                None => println!("Unknown constraint: {}", id),
+            }
+        }
+    }
+
+    /// Weighted sum of every enabled hard or soft constraint
+    pub fn points(&self, view: &ShiftView, hard: bool, weights: &Weights) -> i32 {
+        self.entries
+            .iter()
+            .filter(|e| e.enabled && e.constraint.hard() == hard)
+            .map(|e| {
+                weights.of(e.constraint.as_ref(), view.shift) * e.constraint.evaluate(view, None)
+            })
+            .sum()
+    }
 
-                    _ => {}
+    pub fn reports(&self, view: &ShiftView, weights: &Weights) -> Vec<ConstraintReport> {
+        self.entries
+            .iter()
+            .map(|e| {
+                let constraint = &e.constraint;
+
+                let mut report = constraint_report(
+                    constraint.id(),
+                    constraint.name(),
+                    constraint.hard(),
+                    Some(view.shift),
+                    weights.of(constraint.as_ref(), view.shift),
+                    |violations| constraint.evaluate(view, violations),
+                );
+                report.explanation = constraint.explain().to_string();
+                report.enabled = e.enabled;
+
+                report
+            })
+            .collect()
+    }
+}
+
+impl Default for ConstraintRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        let builtins = [
+            FnConstraint {
+                id: "repeating_teachers",
+                name: "Repeating teachers",
+                hard: true,
+                explanation: "A teacher can't teach multiple classes in the same period",
+                scope: Scope::Slot,
+                evaluate: |v, violations| hard_repeating_teachers(v.timetable, v.shift, violations),
+                evaluate_part: |v, part, violations| {
+                    repeating_teachers_at(v.timetable, v.shift, part as u32, violations)
                 },
-                Slot::Double { first, second, .. } => {
-                    match first {
-                        SlotData::PartiallyFilled { teacher, .. } => {
-                            if seen_teachers.contains(&teacher) {
-                                points += 1;
-
-                                if debug {
-                                    println!("  {}", timetable.data.teachers[teacher].name);
-                                }
-                            } else {
-                                seen_teachers.push(teacher);
-                            }
-                        }
+            },
+            FnConstraint {
+                id: "holes",
+                name: "Holes",
+                hard: true,
+                explanation: "Classes shouldn't have empty periods in the middle of the day",
+                scope: Scope::ClassDay,
+                evaluate: |v, violations| {
+                    hard_holes_in_class_timetable(v.timetable, v.shift, violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    let (class_slots, day) = class_day(v, part);
+                    class_day_holes(v.timetable, class_slots, day, violations)
+                },
+            },
+            FnConstraint {
+                id: "too_many_kinds",
+                name: "Too many subjects of same kind",
+                hard: true,
+                explanation:
+                    "There must be enough rooms of a kind for every lesson held in the same period",
+                scope: Scope::Slot,
+                evaluate: |v, violations| {
+                    hard_too_many_subjects_of_same_kind(v.timetable, v.shift, violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    let kinds = &v.timetable.data.lookups().kinds_by_scarcity;
+                    too_many_kinds_at(v.timetable, v.shift, kinds, part as u32, violations)
+                },
+            },
+            FnConstraint {
+                id: "block_classes",
+                name: "Block classes",
+                hard: true,
+                explanation:
+                    "Relations with blocks must be held in consecutive lessons of those lengths",
+                scope: Scope::Class,
+                evaluate: |v, violations| hard_block_classes(v.timetable, v.shift, violations),
+                evaluate_part: |v, part, violations| {
+                    let class_slots = &v.timetable.table(v.shift)[part];
+                    class_blocks(v.timetable, v.shift, class_slots, violations)
+                },
+            },
+            FnConstraint {
+                id: "specific_subject_days",
+                name: "Specific subject days",
+                hard: true,
+                explanation:
+                    "Subjects of a subject days rule must be held within its number of days",
+                scope: Scope::Class,
+                evaluate: |v, violations| {
+                    hard_specific_subject_days(v.timetable, v.shift, violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    let class_slots = &v.timetable.table(v.shift)[part];
+                    class_subject_days(v.timetable, class_slots, violations)
+                },
+            },
+            FnConstraint {
+                id: "subject_per_day_limits",
+                name: "Subject per day limits",
+                hard: true,
+                explanation: "A class can't have more lessons of a subject per day than its limit",
+                scope: Scope::ClassDay,
+                evaluate: |v, violations| {
+                    hard_subject_per_day_limits(v.timetable, v.shift, violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    let (class_slots, day) = class_day(v, part);
+                    class_day_subject_limits(
+                        v.timetable,
+                        v.timetable.data.lookups().limit_overrides(v.shift),
+                        class_slots,
+                        day,
+                        violations,
+                    )
+                },
+            },
+            FnConstraint {
+                id: "subject_holes",
+                name: "Subject holes",
+                hard: true,
+                explanation: "Lessons of the same subject during a day must be consecutive",
+                scope: Scope::ClassDay,
+                evaluate: |v, violations| hard_subject_holes(v.timetable, v.shift, violations),
+                evaluate_part: |v, part, violations| {
+                    let (class_slots, day) = class_day(v, part);
+                    class_day_subject_holes(v.timetable, class_slots, day, violations)
+                },
+            },
+            FnConstraint {
+                id: "teacher_shift_spread",
+                name: "Teacher shift spread",
+                hard: true,
+                explanation: "Teachers only teach during the shifts they're available for each day",
+                scope: Scope::ClassDay,
+                evaluate: |v, violations| {
+                    hard_teacher_shift_spread(v.timetable, v.shift, violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    let (class_slots, day) = class_day(v, part);
+                    class_day_shift_spread(
+                        v.timetable,
+                        v.shift,
+                        &v.timetable.data.lookups().teacher_spread,
+                        class_slots,
+                        day,
+                        violations,
+                    )
+                },
+            },
+            FnConstraint {
+                id: "hard_teacher_unavailability",
+                name: "Teacher unavailability",
+                hard: true,
+                explanation: "Teachers can't teach during periods marked as unavailable",
+                scope: Scope::Slot,
+                evaluate: |v, violations| {
+                    hard_teacher_unavailability(v.timetable, v.shift, violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    teacher_unavailability_at(v.timetable, v.shift, true, part as u32, violations)
+                },
+            },
+            FnConstraint {
+                id: "subject_times",
+                name: "Subject times",
+                hard: true,
+                explanation: "Subjects are held only when their hard time rules allow it",
+                scope: Scope::ClassDay,
+                evaluate: |v, violations| hard_subject_times(v.timetable, v.shift, violations),
+                evaluate_part: |v, part, violations| {
+                    let (class_slots, day) = class_day(v, part);
+                    let preferences = v.timetable.data.lookups().subject_times(true);
+                    class_day_subject_times(v.timetable, preferences, class_slots, day, violations)
+                },
+            },
+            FnConstraint {
+                id: "class_spread",
+                name: "Class spread",
+                hard: false,
+                explanation: "Lessons of a class should be spread evenly across the week",
+                scope: Scope::Whole,
+                evaluate: |v, violations| soft_class_spread(v.timetable, v.shift, violations),
+                evaluate_part: |v, _, violations| {
+                    soft_class_spread(v.timetable, v.shift, violations)
+                },
+            },
+            FnConstraint {
+                id: "teacher_class_spread",
+                name: "Teacher class spread",
+                hard: false,
+                explanation: "Lessons of a teacher should be spread evenly across the week",
+                scope: Scope::Teacher,
+                evaluate: |v, violations| {
+                    soft_teacher_class_spread(v.timetable, v.teacher_table(), violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    soft_teacher_class_spread(v.timetable, &vec![teacher_part(v, part)], violations)
+                },
+            },
+            FnConstraint {
+                id: "teacher_holes",
+                name: "Teacher holes",
+                hard: false,
+                explanation:
+                    "Teachers shouldn't have more than one empty period in the middle of the day",
+                scope: Scope::Teacher,
+                evaluate: |v, violations| {
+                    soft_holes_in_teacher_timetable(v.timetable, v.teacher_table(), violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    teacher_holes(v.timetable, part, &teacher_part(v, part), violations)
+                },
+            },
+            FnConstraint {
+                id: "preferred_subject_times",
+                name: "Soft preferred subject times",
+                hard: false,
+                explanation: "Subjects should be held when their soft time rules allow it",
+                scope: Scope::ClassDay,
+                evaluate: |v, violations| {
+                    soft_preferred_subject_times(v.timetable, v.shift, violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    let (class_slots, day) = class_day(v, part);
+                    let preferences = v.timetable.data.lookups().subject_times(false);
+                    class_day_subject_times(v.timetable, preferences, class_slots, day, violations)
+                },
+            },
+            FnConstraint {
+                id: "soft_teacher_unavailability",
+                name: "Teacher unavailability",
+                hard: false,
+                explanation: "Teachers would rather not teach during periods marked as unavailable",
+                scope: Scope::Slot,
+                evaluate: |v, violations| {
+                    soft_teacher_unavailability(v.timetable, v.shift, violations)
+                },
+                evaluate_part: |v, part, violations| {
+                    teacher_unavailability_at(v.timetable, v.shift, false, part as u32, violations)
+                },
+            },
+        ];
 
-                        _ => {}
-                    }
+        for constraint in builtins {
+            registry.register(Box::new(constraint), true);
+        }
+
+        // rooms are only assigned at the very end by `fill_rooms`, so they aren't part of the hard cost
+        registry.register(
+            Box::new(FnConstraint {
+                id: "repeating_rooms",
+                name: "Repeating rooms",
+                hard: true,
+                explanation: "A room can't be used by multiple classes in the same period",
+                scope: Scope::Slot,
+                evaluate: |v, violations| repeating_rooms(v.timetable, v.shift, violations),
+                evaluate_part: |v, part, violations| {
+                    repeating_rooms_at(v.timetable, v.shift, part as u32, violations)
+                },
+            }),
+            false,
+        );
+
+        registry
+    }
+}
+
+impl fmt::Debug for ConstraintRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.entries.iter().map(|e| (e.constraint.id(), e.enabled)))
+            .finish()
+    }
+}
+
+/// Lookups the constraints build from the timetable data, kept with the data so they're only built once per change
+#[derive(Clone, Debug)]
+pub struct DataLookups {
+    pub kinds_by_scarcity: Vec<String>,
+    pub teacher_spread: Vec<Option<[DayAvailability; 5]>>,
+    limit_overrides: [HashMap<(usize, usize), u32>; 2], // by shift
+    subject_times: [Vec<Vec<SubjectTime>>; 2],          // soft and hard rules
+}
+
+impl DataLookups {
+    pub fn new(data: &TimetableData) -> Self {
+        Self {
+            kinds_by_scarcity: data.kinds_by_scarcity(),
+            teacher_spread: teacher_spread(data),
+            limit_overrides: [
+                limit_overrides(data, Shift::First),
+                limit_overrides(data, Shift::Second),
+            ],
+            subject_times: [
+                subject_preferences(data, false),
+                subject_preferences(data, true),
+            ],
+        }
+    }
+
+    pub fn limit_overrides(&self, shift: Shift) -> &HashMap<(usize, usize), u32> {
+        match shift {
+            Shift::First => &self.limit_overrides[0],
+            Shift::Second => &self.limit_overrides[1],
+        }
+    }
+
+    /// Hard or soft subject time rules, indexed by subject
+    pub fn subject_times(&self, hard: bool) -> &[Vec<SubjectTime>] {
+        &self.subject_times[hard as usize]
+    }
+}
+
+/// Cost of every part of every enabled constraint of a shift, so a move only has to re-evaluate the parts it touched
+#[derive(Clone, Debug)]
+pub struct ShiftScores {
+    parts: Vec<Vec<i32>>, // indexed by registry entry, empty for disabled entries
+    totals: Vec<i32>,     // unweighted
+    pub hard: i32,
+    pub soft: i32,
+}
+
+/// Parts re-evaluated after a move, only applied to the scores once the move is accepted
+#[derive(Clone, Debug)]
+pub struct ScoreUpdate {
+    parts: Vec<(usize, usize, i32)>, // (entry, part, cost)
+    totals: Vec<i32>,
+    pub hard: i32,
+    pub soft: i32,
+}
+
+impl ShiftScores {
+    pub fn new(registry: &ConstraintRegistry, view: &ShiftView, weights: &Weights) -> Self {
+        let parts: Vec<Vec<i32>> = registry
+            .entries
+            .iter()
+            .map(|e| {
+                if !e.enabled {
+                    return vec![];
+                }
+
+                (0..e.constraint.scope().part_count(view))
+                    .map(|part| e.constraint.evaluate_part(view, part, None))
+                    .collect()
+            })
+            .collect();
+
+        let totals: Vec<i32> = parts.iter().map(|p| p.iter().sum()).collect();
+        let (hard, soft) = weighted(registry, &totals, view.shift, weights);
+
+        Self {
+            parts,
+            totals,
+            hard,
+            soft,
+        }
+    }
+
+    /// Scores of `view`, which only differs from the scored timetable by the move
+    pub fn rescore(
+        &self,
+        registry: &ConstraintRegistry,
+        view: &ShiftView,
+        weights: &Weights,
+        mv: &Move,
+    ) -> ScoreUpdate {
+        let mut parts: Vec<(usize, usize, i32)> = vec![];
+        let mut totals = self.totals.clone();
+
+        for (i, e) in registry.entries.iter().enumerate() {
+            if !e.enabled {
+                continue;
+            }
+
+            for part in e.constraint.scope().touched(view, mv) {
+                let cost = e.constraint.evaluate_part(view, part, None);
+
+                totals[i] += cost - self.parts[i][part];
+                parts.push((i, part, cost));
+            }
+        }
+
+        let (hard, soft) = weighted(registry, &totals, view.shift, weights);
+
+        ScoreUpdate {
+            parts,
+            totals,
+            hard,
+            soft,
+        }
+    }
+
+    /// Unweighted cost of every registry entry, 0 for disabled entries
+    pub fn totals(&self) -> &[i32] {
+        &self.totals
+    }
+
+    pub fn apply(&mut self, update: ScoreUpdate) {
+        for (i, part, cost) in update.parts {
+            self.parts[i][part] = cost;
+        }
+
+        self.totals = update.totals;
+        self.hard = update.hard;
+        self.soft = update.soft;
+    }
+}
+
+/// Weighted hard and soft cost from the unweighted cost of each registry entry
+fn weighted(
+    registry: &ConstraintRegistry,
+    totals: &[i32],
+    shift: Shift,
+    weights: &Weights,
+) -> (i32, i32) {
+    let mut hard = 0;
+    let mut soft = 0;
+
+    for (e, total) in registry.entries.iter().zip(totals) {
+        if !e.enabled {
+            continue;
+        }
+
+        let points = weights.of(e.constraint.as_ref(), shift) * total;
+        if e.constraint.hard() {
+            hard += points;
+        } else {
+            soft += points;
+        }
+    }
+
+    (hard, soft)
+}
+
+/// Weighted cost of both shifts of a timetable, in the same order as the annealer's log line
+#[derive(Clone, Copy, PartialEq, Serialize, Debug, Default)]
+pub struct CostBreakdown {
+    pub teacher_shifts: i32,
+    pub hard_1: i32,
+    pub soft_1: i32,
+    pub hard_2: i32,
+    pub soft_2: i32,
+}
+
+impl CostBreakdown {
+    /// Cost the annealer minimizes for a single shift, the teacher shifts cost is shared by both
+    pub fn shift(&self, shift: Shift) -> i32 {
+        match shift {
+            Shift::First => self.hard_1 + self.soft_1 + self.teacher_shifts,
+            Shift::Second => self.hard_2 + self.soft_2 + self.teacher_shifts,
+        }
+    }
+
+    pub fn total(&self) -> i32 {
+        self.teacher_shifts + self.hard_1 + self.soft_1 + self.hard_2 + self.soft_2
+    }
+}
+
+impl fmt::Display for CostBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{: >3}]   [{: >3}, {: >3}]    [{: >3}, {: >3}]",
+            self.teacher_shifts, self.hard_1, self.soft_1, self.hard_2, self.soft_2,
+        )
+    }
+}
+
+/// Same as `ShiftScores` except for `teacher_shifts`, which spans both shifts and is split by day (unweighted)
+#[derive(Clone, Debug)]
+pub struct TeacherShiftsScores {
+    days: Vec<i32>,
+    pub total: i32,
+}
+
+impl TeacherShiftsScores {
+    pub fn new(table1: &[ClassSlots], table2: &[ClassSlots], max_periods_per_day: u32) -> Self {
+        let days: Vec<i32> = (0..5)
+            .map(|day| teacher_shifts_on(table1, table2, max_periods_per_day, day, None))
+            .collect();
+        let total = days.iter().sum();
+
+        Self { days, total }
+    }
+
+    /// Scores of `table1` and `table2`, which only differ from the scored tables by the moves
+    pub fn rescore(
+        &self,
+        table1: &[ClassSlots],
+        table2: &[ClassSlots],
+        max_periods_per_day: u32,
+        moves: &[&Move],
+    ) -> Self {
+        let mut scores = self.clone();
+
+        let mut days: Vec<u32> = moves
+            .iter()
+            .flat_map(|mv| mv.days(max_periods_per_day))
+            .collect();
+        days.sort();
+        days.dedup();
+
+        for day in days {
+            let cost = teacher_shifts_on(table1, table2, max_periods_per_day, day, None);
+
+            scores.total += cost - scores.days[day as usize];
+            scores.days[day as usize] = cost;
+        }
+
+        scores
+    }
+}
+
+/* ==================== */
+/*   HARD CONSTRAINTS   */
+/* ==================== */
+
+/// Increment points by 1 for each room being used by multiple classes in the same period
+pub fn repeating_rooms(
+    timetable: &Timetable,
+    shift: Shift,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    (0..(timetable.max_periods_per_day * 5))
+        .map(|period| repeating_rooms_at(timetable, shift, period, violations.as_deref_mut()))
+        .sum()
+}
+
+fn repeating_rooms_at(
+    timetable: &Timetable,
+    shift: Shift,
+    period: u32,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
+    let mut seen_rooms: Vec<usize> = vec![];
+
+    for class_slots in timetable.table(shift).iter() {
+        let slot_datas = match class_slots.slots[period as usize] {
+            Slot::Single(s) => vec![s],
+            Slot::Double { first, second, .. } => vec![first, second],
+        };
+
+        for slot_data in slot_datas {
+            if let SlotData::PartiallyFilled {
+                teacher,
+                room: Some(r),
+                ..
+            } = slot_data
+            {
+                if seen_rooms.contains(&r) {
+                    points += 1;
+
+                    record(
+                        &mut violations,
+                        1,
+                        Violation {
+                            class: Some(class_slots.class_index as usize),
+                            teacher: Some(teacher),
+                            room: Some(r),
+                            ..Violation::at(timetable.max_periods_per_day, period)
+                        },
+                    );
+                } else {
+                    seen_rooms.push(r);
+                }
+            }
+        }
+    }
+
+    points
+}
+
+/// Increment points by 1 for each teacher teaching multiple classes in the same period
+pub fn hard_repeating_teachers(
+    timetable: &Timetable,
+    shift: Shift,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    (0..(timetable.max_periods_per_day * 5))
+        .map(|period| repeating_teachers_at(timetable, shift, period, violations.as_deref_mut()))
+        .sum()
+}
+
+fn repeating_teachers_at(
+    timetable: &Timetable,
+    shift: Shift,
+    period: u32,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
+    let mut seen_teachers: Vec<usize> = vec![];
 
-                    match second {
-                        SlotData::PartiallyFilled { teacher, .. } => {
-                            if seen_teachers.contains(&teacher) {
-                                points += 1;
-
-                                if debug {
-                                    println!("  {}", timetable.data.teachers[teacher].name);
-                                }
-                            } else {
-                                seen_teachers.push(teacher);
-                            }
-                        }
+    for class_slots in timetable.table(shift).iter() {
+        let slot_datas = match class_slots.slots[period as usize] {
+            Slot::Single(s) => vec![s],
+            Slot::Double { first, second, .. } => vec![first, second],
+        };
+
+        for slot_data in slot_datas {
+            if let SlotData::PartiallyFilled { teacher, .. } = slot_data {
+                if seen_teachers.contains(&teacher) {
+                    points += 1;
 
-                        _ => {}
-                    }
+                    record(
+                        &mut violations,
+                        1,
+                        Violation {
+                            class: Some(class_slots.class_index as usize),
+                            teacher: Some(teacher),
+                            ..Violation::at(timetable.max_periods_per_day, period)
+                        },
+                    );
+                } else {
+                    seen_teachers.push(teacher);
                 }
             }
         }
@@ -146,91 +1041,115 @@ pub fn hard_repeating_teachers(timetable: &Timetable, shift: Shift, debug: bool)
 }
 
 /// Increment points by 1 for every hole in a class timetable
-pub fn hard_holes_in_class_timetable(timetable: &Timetable, shift: Shift) -> i32 {
+pub fn hard_holes_in_class_timetable(
+    timetable: &Timetable,
+    shift: Shift,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
     let mut points: i32 = 0;
 
     for class_slots in timetable.table(shift).iter() {
         for day in 0..5 {
-            let mut empty_slots_single = 0;
-            let mut empty_slots = 0;
-            for period in 0..timetable.max_periods_per_day {
-                let index = day * timetable.max_periods_per_day + period;
-
-                match class_slots.slots[index as usize] {
-                    Slot::Single(SlotData::Empty) => {
-                        empty_slots += 1;
-                        empty_slots_single += 1;
-                    }
+            points += class_day_holes(timetable, class_slots, day, violations.as_deref_mut());
+        }
+    }
 
-                    Slot::Double {
-                        first: SlotData::Empty,
-                        second: SlotData::PartiallyFilled { .. },
-                        ..
-                    }
-                    | Slot::Double {
-                        first: SlotData::PartiallyFilled { .. },
-                        second: SlotData::Empty,
-                        ..
-                    } => empty_slots += 1,
+    points
+}
 
-                    _ => {}
-                }
+fn class_day_holes(
+    timetable: &Timetable,
+    class_slots: &ClassSlots,
+    day: u32,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut empty_slots_single = 0;
+    let mut empty_slots = 0;
+    for period in 0..timetable.max_periods_per_day {
+        let index = day * timetable.max_periods_per_day + period;
+
+        match class_slots.slots[index as usize] {
+            Slot::Single(SlotData::Empty) => {
+                empty_slots += 1;
+                empty_slots_single += 1;
             }
 
-            // just give up right away if the day was entirely empty
-            if empty_slots_single == timetable.max_periods_per_day {
-                continue;
+            Slot::Double {
+                first: SlotData::Empty,
+                second: SlotData::PartiallyFilled { .. },
+                ..
             }
+            | Slot::Double {
+                first: SlotData::PartiallyFilled { .. },
+                second: SlotData::Empty,
+                ..
+            } => empty_slots += 1,
 
-            let mut start = 0;
-            for period in 0..timetable.max_periods_per_day {
-                let index = day * timetable.max_periods_per_day + period;
+            _ => {}
+        }
+    }
 
-                match class_slots.slots[index as usize] {
-                    Slot::Single(_) => break,
-
-                    Slot::Double {
-                        first: SlotData::PartiallyFilled { .. },
-                        second: SlotData::Empty,
-                        ..
-                    } => empty_slots -= 1,
-                    Slot::Double { .. } => break,
-                }
+    // just give up right away if the day was entirely empty
+    if empty_slots_single == timetable.max_periods_per_day {
+        return 0;
+    }
 
-                start += 1;
-            }
+    let mut start = 0;
+    for period in 0..timetable.max_periods_per_day {
+        let index = day * timetable.max_periods_per_day + period;
 
-            let mut one_group_left = false;
-            for period in ((start + 1)..timetable.max_periods_per_day).rev() {
-                let index = day * timetable.max_periods_per_day + period;
+        match class_slots.slots[index as usize] {
+            Slot::Single(_) => break,
 
-                match class_slots.slots[index as usize] {
-                    Slot::Single(SlotData::Empty) => {
-                        if one_group_left {
-                            break;
-                        }
-                        empty_slots -= 1;
-                    }
-                    Slot::Single(_) => break,
-
-                    Slot::Double {
-                        first: SlotData::PartiallyFilled { .. },
-                        second: SlotData::Empty,
-                        ..
-                    } => {
-                        empty_slots -= 1;
-                        one_group_left = true;
-                    }
-                    Slot::Double { .. } => break,
+            Slot::Double {
+                first: SlotData::PartiallyFilled { .. },
+                second: SlotData::Empty,
+                ..
+            } => empty_slots -= 1,
+            Slot::Double { .. } => break,
+        }
+
+        start += 1;
+    }
+
+    let mut one_group_left = false;
+    for period in ((start + 1)..timetable.max_periods_per_day).rev() {
+        let index = day * timetable.max_periods_per_day + period;
+
+        match class_slots.slots[index as usize] {
+            Slot::Single(SlotData::Empty) => {
+                if one_group_left {
+                    break;
                 }
+                empty_slots -= 1;
             }
-
-            // multiply by 2 for higher priority
-            points += empty_slots;
+            Slot::Single(_) => break,
+
+            Slot::Double {
+                first: SlotData::PartiallyFilled { .. },
+                second: SlotData::Empty,
+                ..
+            } => {
+                empty_slots -= 1;
+                one_group_left = true;
+            }
+            Slot::Double { .. } => break,
         }
     }
 
-    points
+    if empty_slots > 0 {
+        record(
+            &mut violations,
+            empty_slots,
+            Violation {
+                class: Some(class_slots.class_index as usize),
+                ..Violation::on(day)
+            },
+        );
+    }
+
+    // multiply by 2 for higher priority
+    empty_slots
 }
 
 /// Increment points by 1 for each period during which too many classes are being held with the same kind
@@ -239,131 +1158,117 @@ pub fn hard_holes_in_class_timetable(timetable: &Timetable, shift: Shift) -> i32
 pub fn hard_too_many_subjects_of_same_kind(
     timetable: &Timetable,
     shift: Shift,
-    debug: bool,
+    mut violations: Option<&mut Vec<Violation>>,
 ) -> i32 {
-    let mut points = 0;
+    // same order in which `Timetable::fill_rooms` assigns rooms
+    let kinds = &timetable.data.lookups().kinds_by_scarcity;
 
-    for period in 0..(timetable.max_periods_per_day * 5) {
-        let mut subject_kinds_count: HashMap<String, u32> = HashMap::new();
+    (0..(timetable.max_periods_per_day * 5))
+        .map(|period| too_many_kinds_at(timetable, shift, kinds, period, violations.as_deref_mut()))
+        .sum()
+}
 
-        for class_slots in timetable.table(shift).iter() {
-            match class_slots.slots[period as usize] {
-                Slot::Single(s) => match s {
-                    SlotData::PartiallyFilled { subject, .. } => {
-                        subject_kinds_count
-                            .entry(timetable.data.subjects[subject].kind.clone())
-                            .and_modify(|c| *c += 1)
-                            .or_insert(1);
-                    }
+fn too_many_kinds_at(
+    timetable: &Timetable,
+    shift: Shift,
+    kinds: &[String],
+    period: u32,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
 
-                    _ => {}
-                },
-                Slot::Double { first, second, .. } => {
-                    match first {
-                        SlotData::PartiallyFilled { subject, .. } => {
-                            subject_kinds_count
-                                .entry(timetable.data.subjects[subject].kind.clone())
-                                .and_modify(|c| *c += 1)
-                                .or_insert(1);
-                        }
+    let mut subject_kinds_count: HashMap<String, u32> = HashMap::new();
 
-                        _ => {}
-                    }
+    for class_slots in timetable.table(shift).iter() {
+        match class_slots.slots[period as usize] {
+            Slot::Single(s) => {
+                if let SlotData::PartiallyFilled { subject, .. } = s {
+                    subject_kinds_count
+                        .entry(timetable.data.subjects[subject].kind.clone())
+                        .and_modify(|c| *c += 1)
+                        .or_insert(1);
+                }
+            }
+            Slot::Double { first, second, .. } => {
+                if let SlotData::PartiallyFilled { subject, .. } = first {
+                    subject_kinds_count
+                        .entry(timetable.data.subjects[subject].kind.clone())
+                        .and_modify(|c| *c += 1)
+                        .or_insert(1);
+                }
 
-                    match second {
-                        SlotData::PartiallyFilled { subject, .. } => {
-                            subject_kinds_count
-                                .entry(timetable.data.subjects[subject].kind.clone())
-                                .and_modify(|c| *c += 1)
-                                .or_insert(1);
-                        }
+                if let SlotData::PartiallyFilled { subject, .. } = second {
+                    subject_kinds_count
+                        .entry(timetable.data.subjects[subject].kind.clone())
+                        .and_modify(|c| *c += 1)
+                        .or_insert(1);
+                }
+            }
+        }
+    }
 
-                        _ => {}
-                    }
+    /*
+    let mut kinds: Vec<(&String, &u32)> = subject_kinds_count.iter().collect();
+    kinds.sort_by(|a, b| a.1.cmp(b.1));
+    let mut used_rooms: Vec<usize> = vec![];
+    for (kind, count) in kinds {
+        let mut found_rooms = true;
+        for _ in 0..(*count) {
+            let mut found_room = false;
+            for i in 0..timetable.data.rooms.len() {
+                if !used_rooms.contains(&i)
+                    && timetable.data.rooms[i].kinds.contains(&kind.to_string())
+                {
+                    used_rooms.push(i);
+                    found_room = true;
+                    break;
                 }
             }
+
+            if !found_room {
+                found_rooms = false;
+                break;
+            }
+        }
+
+        if !found_rooms {
+            points += 1;
         }
+    }
+    */
+
+    let mut used_rooms: Vec<usize> = vec![];
+    for kind in kinds.iter() {
+        let mut found_rooms = false;
+        if let Some(n) = subject_kinds_count.get(kind) {
+            for _ in 0..(*n) {
+                found_rooms = false;
 
-        /*
-        let mut kinds: Vec<(&String, &u32)> = subject_kinds_count.iter().collect();
-        kinds.sort_by(|a, b| a.1.cmp(b.1));
-        let mut used_rooms: Vec<usize> = vec![];
-        for (kind, count) in kinds {
-            let mut found_rooms = true;
-            for _ in 0..(*count) {
-                let mut found_room = false;
+                //let mut found_room = false;
                 for i in 0..timetable.data.rooms.len() {
-                    if !used_rooms.contains(&i)
-                        && timetable.data.rooms[i].kinds.contains(&kind.to_string())
-                    {
+                    if !used_rooms.contains(&i) && timetable.data.rooms[i].kinds.contains(kind) {
                         used_rooms.push(i);
-                        found_room = true;
+                        //found_room = true;
+                        found_rooms = true;
                         break;
                     }
                 }
 
+                /*
                 if !found_room {
-                    found_rooms = false;
-                    break;
+                    points += 1;
                 }
+                */
             }
 
             if !found_rooms {
                 points += 1;
-            }
-        }
-        */
-
-        let mut used_rooms: Vec<usize> = vec![];
-        for kind in [
-            "masinska",
-            "14",
-            "sd",
-            "sala",
-            "regular",
-            "computer",
-            "computer-regular",
-            "14-23",
-            "masinska-14",
-            "masinska-sd",
-            "masinska-computer",
-            "masinska-regular",
-            "masinska-regular-sd",
-        ] {
-            let mut found_rooms = false;
-            match subject_kinds_count.get(kind) {
-                Some(n) => {
-                    for _ in 0..(*n) {
-                        found_rooms = false;
-
-                        //let mut found_room = false;
-                        for i in 0..timetable.data.rooms.len() {
-                            if !used_rooms.contains(&i)
-                                && timetable.data.rooms[i].kinds.contains(&kind.to_string())
-                            {
-                                used_rooms.push(i);
-                                //found_room = true;
-                                found_rooms = true;
-                                break;
-                            }
-                        }
-
-                        /*
-                        if !found_room {
-                            points += 1;
-                        }
-                        */
-                    }
-
-                    if !found_rooms {
-                        points += 1;
 
-                        if debug {
-                            println!("  {}: {}", period, kind);
-                        }
-                    }
-                }
-                None => {}
+                record(
+                    &mut violations,
+                    1,
+                    Violation::at(timetable.max_periods_per_day, period),
+                );
             }
         }
     }
@@ -371,192 +1276,454 @@ pub fn hard_too_many_subjects_of_same_kind(
     points
 }
 
-pub fn hard_block_classes(timetable: &Timetable, shift: Shift) -> i32 {
-    let mut points: i32 = 0;
-    /*
+/// Increment points by 1 for each relation (or group of a split relation) whose lessons aren't held in the blocks it requires
+///
+/// Example: A relation with blocks 2+1 should have 2 consecutive lessons during one day and a single lesson during another
+pub fn hard_block_classes(
+    timetable: &Timetable,
+    shift: Shift,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    timetable
+        .table(shift)
+        .iter()
+        .map(|class_slots| class_blocks(timetable, shift, class_slots, violations.as_deref_mut()))
+        .sum()
+}
 
-    let subjects = vec![67, 66, 64, 58];
+fn class_blocks(
+    timetable: &Timetable,
+    shift: Shift,
+    class_slots: &ClassSlots,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points: i32 = 0;
 
-    for class_slots in timetable.table(shift).iter() {
-        for subject in subjects.iter() {
-            let mut block_found = false;
-            let mut subject_exists = false;
+    for relation in timetable.data.relations.iter() {
+        if !shift.equals(relation.shift)
+            || relation.blocks.is_empty()
+            || relation.class != class_slots.class_index as usize
+        {
+            continue;
+        }
 
-            for day in 0..5 {
-                if block_found {
-                    break;
-                }
+        let mut blocks = relation.blocks.clone();
+        blocks.sort();
 
-                for period in 0..timetable.max_periods_per_day {
-                    let index = day * timetable.max_periods_per_day + period;
-
-                    match class_slots.slots[index as usize] {
-                        Slot::Single(SlotData::PartiallyFilled {
-                            subject: subject_out,
-                            ..
-                        }) => {
-                            if subject_out == *subject {
-                                subject_exists = true;
-
-                                if period < timetable.max_periods_per_day - 1 {
-                                    match class_slots.slots[index as usize + 1] {
-                                        Slot::Single(SlotData::PartiallyFilled {
-                                            subject: subject_in,
-                                            ..
-                                        }) => {
-                                            if subject_in == *subject {
-                                                block_found = true;
-                                                break;
-                                            }
-                                        }
-
-                                        _ => {}
-                                    }
-                                }
-                            }
-                        }
+        // single relations are held in single slots, split ones in either half of double slots
+        let groups = match relation.per_week_second {
+            None => vec![Group::Whole],
+            Some(_) => vec![Group::First, Group::Second],
+        };
 
-                        _ => {}
-                    }
-                }
+        for group in groups {
+            let mut runs = lesson_runs(timetable, class_slots, group, relation);
+            if runs.is_empty() {
+                continue;
             }
 
-            if subject_exists && !block_found {
+            runs.sort();
+            if runs != blocks {
                 points += 1;
+
+                record(
+                    &mut violations,
+                    1,
+                    Violation {
+                        class: Some(relation.class),
+                        teacher: Some(relation.teacher),
+                        ..Default::default()
+                    },
+                );
             }
         }
     }
 
-    */
     points
 }
 
-pub fn hard_specific_subject_days(timetable: &Timetable, shift: Shift) -> i32 {
+#[derive(Clone, Copy)]
+enum Group {
+    Whole,
+    First,
+    Second,
+}
+
+/// Lengths of every run of consecutive lessons of the relation within a day, for a single group
+fn lesson_runs(
+    timetable: &Timetable,
+    class_slots: &ClassSlots,
+    group: Group,
+    relation: &Relation,
+) -> Vec<u32> {
+    let mut runs: Vec<u32> = vec![];
+
+    for day in 0..5 {
+        let mut run = 0;
+
+        for period in 0..timetable.max_periods_per_day {
+            let index = day * timetable.max_periods_per_day + period;
+
+            let slot_data = match (group, class_slots.slots[index as usize]) {
+                (Group::Whole, Slot::Single(s)) => s,
+                (Group::First, Slot::Double { first, .. }) => first,
+                (Group::Second, Slot::Double { second, .. }) => second,
+                _ => SlotData::Empty,
+            };
+
+            match slot_data {
+                SlotData::PartiallyFilled {
+                    teacher, subject, ..
+                } if teacher == relation.teacher && subject == relation.subject => run += 1,
+                _ => {
+                    if run > 0 {
+                        runs.push(run);
+                    }
+                    run = 0;
+                }
+            }
+        }
+
+        if run > 0 {
+            runs.push(run);
+        }
+    }
+
+    runs
+}
+
+/// Increment points by 1 for each day over the limit of a subject days constraint, for each of its classes
+///
+/// Example: Practical subjects should leave at least 1 day free for S4A, but are held every day of the week
+pub fn hard_specific_subject_days(
+    timetable: &Timetable,
+    shift: Shift,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    timetable
+        .table(shift)
+        .iter()
+        .map(|class_slots| class_subject_days(timetable, class_slots, violations.as_deref_mut()))
+        .sum()
+}
+
+fn class_subject_days(
+    timetable: &Timetable,
+    class_slots: &ClassSlots,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
     let mut points = 0;
-    /*
 
-    let subjects = vec![67, 66, 65, 64, 63, 62, 61, 60, 59, 58];
-    let classes = vec![7, 6, 5, 15, 14, 13];
+    for constraint in timetable.data.subject_days.iter() {
+        if !constraint
+            .classes
+            .contains(&(class_slots.class_index as usize))
+        {
+            continue;
+        }
 
-    for class in classes.iter() {
-        let mut days_without_subjects = 0;
+        let mut occupied_days: Vec<u32> = vec![];
 
         for day in 0..5 {
-            days_without_subjects += 1;
-
             for period in 0..timetable.max_periods_per_day {
                 let index = day * timetable.max_periods_per_day + period;
 
-                match timetable.table(shift)[*class as usize].slots[index as usize] {
-                    Slot::Single(s) => match s {
+                let occupied = match class_slots.slots[index as usize] {
+                    Slot::Single(SlotData::PartiallyFilled { subject, .. }) => {
+                        constraint.subjects.contains(&subject)
+                    }
+                    Slot::Double { first, second, .. } => [first, second].iter().any(|s| match s {
                         SlotData::PartiallyFilled { subject, .. } => {
-                            if subjects.contains(&subject) {
-                                days_without_subjects -= 1;
-                                break;
-                            }
+                            constraint.subjects.contains(subject)
                         }
+                        SlotData::Empty => false,
+                    }),
 
-                        _ => {}
-                    },
+                    _ => false,
+                };
 
-                    _ => {}
+                if occupied {
+                    occupied_days.push(day);
+                    break;
                 }
             }
         }
 
-        if days_without_subjects == 0 {
-            points += 1;
+        let over = match constraint.kind {
+            SubjectDaysKind::MinFreeDays => {
+                (occupied_days.len() as i32) - (5 - constraint.days as i32)
+            }
+            SubjectDaysKind::MaxDays => (occupied_days.len() as i32) - constraint.days as i32,
+        };
+
+        if over > 0 {
+            points += over;
+
+            // the whole overflow is put on the last occupied day, the rest only mark where it's held
+            let last_day = occupied_days.last().copied();
+            for day in occupied_days {
+                record(
+                    &mut violations,
+                    if Some(day) == last_day { over } else { 0 },
+                    Violation {
+                        class: Some(class_slots.class_index as usize),
+                        ..Violation::on(day)
+                    },
+                );
+            }
         }
     }
 
-    */
     points
 }
 
-pub fn hard_subject_per_day_limits(timetable: &Timetable, shift: Shift) -> i32 {
+/// Increment points by 1 for each day a class (or either of its groups) has more lessons of a subject than allowed
+///
+/// The limit comes from the class' relation if it sets one, otherwise from the subject itself
+pub fn hard_subject_per_day_limits(
+    timetable: &Timetable,
+    shift: Shift,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
     let mut points = 0;
 
-    let subjects2 = vec![67, 66, 64, 58];
-    // let subjects1 = vec![59];
+    let limit_overrides = timetable.data.lookups().limit_overrides(shift);
 
     for class_slots in timetable.table(shift).iter() {
         for day in 0..5 {
-            let mut subject_counts: HashMap<usize, i32> = HashMap::new();
+            points += class_day_subject_limits(
+                timetable,
+                limit_overrides,
+                class_slots,
+                day,
+                violations.as_deref_mut(),
+            );
+        }
+    }
 
-            for period in 0..timetable.max_periods_per_day {
-                let index = day * timetable.max_periods_per_day + period;
+    points
+}
 
-                match class_slots.slots[index as usize] {
-                    Slot::Single(SlotData::PartiallyFilled { subject, .. }) => {
-                        subject_counts
-                            .entry(subject)
-                            .and_modify(|c| *c += 1)
-                            .or_insert(1);
-                    }
+/// (class, subject) -> limit, for every relation of the shift that sets its own limit
+fn limit_overrides(data: &TimetableData, shift: Shift) -> HashMap<(usize, usize), u32> {
+    let mut limit_overrides: HashMap<(usize, usize), u32> = HashMap::new();
+    for relation in data.relations.iter() {
+        if let Some(limit) = relation.max_per_day {
+            if shift.equals(relation.shift) {
+                limit_overrides.insert((relation.class, relation.subject), limit);
+            }
+        }
+    }
 
-                    _ => {}
-                }
+    limit_overrides
+}
+
+fn class_day_subject_limits(
+    timetable: &Timetable,
+    limit_overrides: &HashMap<(usize, usize), u32>,
+    class_slots: &ClassSlots,
+    day: u32,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
+    let class = class_slots.class_index as usize;
+
+    let mut first_group_counts: HashMap<usize, u32> = HashMap::new();
+    let mut second_group_counts: HashMap<usize, u32> = HashMap::new();
+
+    for period in 0..timetable.max_periods_per_day {
+        let index = day * timetable.max_periods_per_day + period;
+
+        match class_slots.slots[index as usize] {
+            Slot::Single(SlotData::PartiallyFilled { subject, .. }) => {
+                *first_group_counts.entry(subject).or_insert(0) += 1;
+                *second_group_counts.entry(subject).or_insert(0) += 1;
             }
+            Slot::Double { first, second, .. } => {
+                if let SlotData::PartiallyFilled { subject, .. } = first {
+                    *first_group_counts.entry(subject).or_insert(0) += 1;
+                }
 
-            for subject in subject_counts.keys() {
-                /*
-                if subjects1.contains(subject) {
-                    if subject_counts[subject] > 1 {
-                        points += 1;
-                    }
+                if let SlotData::PartiallyFilled { subject, .. } = second {
+                    *second_group_counts.entry(subject).or_insert(0) += 1;
                 }
-                */
+            }
+
+            _ => {}
+        }
+    }
+
+    let mut subjects: Vec<&usize> = first_group_counts.keys().collect();
+    for subject in second_group_counts.keys() {
+        if !subjects.contains(&subject) {
+            subjects.push(subject);
+        }
+    }
+
+    for subject in subjects {
+        let limit = match limit_overrides.get(&(class, *subject)) {
+            Some(limit) => *limit,
+            None => match timetable.data.subjects[*subject].max_per_day {
+                Some(limit) => limit,
+                None => continue,
+            },
+        };
+
+        let count = first_group_counts
+            .get(subject)
+            .max(second_group_counts.get(subject))
+            .copied()
+            .unwrap_or(0);
+
+        if count > limit {
+            points += 1;
+
+            record(
+                &mut violations,
+                1,
+                Violation {
+                    class: Some(class),
+                    ..Violation::on(day)
+                },
+            );
+        }
+    }
+
+    points
+}
+
+/// Increment points by 1 for each lesson of a subject that already had lessons earlier in the day, but not right before it
+pub fn hard_subject_holes(
+    timetable: &Timetable,
+    shift: Shift,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
+
+    for class_slots in timetable.table(shift).iter() {
+        for day in 0..5 {
+            points +=
+                class_day_subject_holes(timetable, class_slots, day, violations.as_deref_mut());
+        }
+    }
+
+    points
+}
+
+fn class_day_subject_holes(
+    timetable: &Timetable,
+    class_slots: &ClassSlots,
+    day: u32,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
+
+    let mut last_subject: i32 = -1;
+    let mut seen_subjects: Vec<usize> = vec![];
 
-                if subjects2.contains(subject) {
-                    if subject_counts[subject] > 2 {
+    for period in 0..timetable.max_periods_per_day {
+        let index = day * timetable.max_periods_per_day + period;
+
+        match class_slots.slots[index as usize] {
+            Slot::Single(s) => {
+                if let SlotData::PartiallyFilled { subject, .. } = s {
+                    if seen_subjects.contains(&subject) && subject != last_subject as usize {
                         points += 1;
+
+                        record(
+                            &mut violations,
+                            1,
+                            Violation {
+                                class: Some(class_slots.class_index as usize),
+                                ..Violation::at(timetable.max_periods_per_day, index)
+                            },
+                        );
                     }
+
+                    seen_subjects.push(subject);
+                    last_subject = subject as i32;
                 }
             }
+
+            Slot::Double { .. } => {}
         }
     }
 
     points
 }
 
-pub fn hard_subject_holes(timetable: &Timetable, shift: Shift, debug: bool) -> i32 {
+/// Increment points by 1 for each period a teacher teaches on a day they aren't available for the given shift
+pub fn hard_teacher_shift_spread(
+    timetable: &Timetable,
+    shift: Shift,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
     let mut points = 0;
 
+    let teacher_spread = &timetable.data.lookups().teacher_spread;
+
     for class_slots in timetable.table(shift).iter() {
         for day in 0..5 {
-            let mut last_subject: i32 = -1;
-            let mut seen_subjects: Vec<usize> = vec![];
+            points += class_day_shift_spread(
+                timetable,
+                shift,
+                teacher_spread,
+                class_slots,
+                day,
+                violations.as_deref_mut(),
+            );
+        }
+    }
 
-            for period in 0..timetable.max_periods_per_day {
-                let index = day * timetable.max_periods_per_day + period;
+    points
+}
 
-                match class_slots.slots[index as usize] {
-                    Slot::Single(s) => match s {
-                        SlotData::PartiallyFilled { subject, .. } => {
-                            if seen_subjects.contains(&subject) {
-                                if subject != last_subject as usize {
-                                    points += 1;
-
-                                    if debug {
-                                        println!(
-                                            "  {}: {}",
-                                            timetable.data.classes
-                                                [class_slots.class_index as usize]
-                                                .name,
-                                            timetable.data.subjects[subject].name
-                                        );
-                                    }
-                                }
-                            }
-
-                            seen_subjects.push(subject);
-                            last_subject = subject as i32;
-                        }
+/// Indexed by teacher, teachers without an entry are available for both shifts every day
+fn teacher_spread(data: &TimetableData) -> Vec<Option<[DayAvailability; 5]>> {
+    let mut teacher_spread: Vec<Option<[DayAvailability; 5]>> = vec![None; data.teachers.len()];
+    for availability in data.teacher_availability.iter() {
+        if let Some(spread) = teacher_spread.get_mut(availability.teacher) {
+            *spread = Some(availability.days);
+        }
+    }
+
+    teacher_spread
+}
+
+fn class_day_shift_spread(
+    timetable: &Timetable,
+    shift: Shift,
+    teacher_spread: &[Option<[DayAvailability; 5]>],
+    class_slots: &ClassSlots,
+    day: u32,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
+
+    for period in 0..timetable.max_periods_per_day {
+        let index = day * timetable.max_periods_per_day + period;
 
-                        _ => {}
-                    },
+        let slot_datas = match class_slots.slots[index as usize] {
+            Slot::Single(s) => vec![s],
+            Slot::Double { first, second, .. } => vec![first, second],
+        };
+
+        for slot_data in slot_datas {
+            if let SlotData::PartiallyFilled { teacher, .. } = slot_data {
+                if let Some(days) = teacher_spread[teacher] {
+                    if !days[day as usize].allows_shift(shift.to_i32()) {
+                        points += 1;
 
-                    Slot::Double { .. } => {}
+                        record(
+                            &mut violations,
+                            1,
+                            Violation {
+                                class: Some(class_slots.class_index as usize),
+                                teacher: Some(teacher),
+                                ..Violation::at(timetable.max_periods_per_day, index)
+                            },
+                        );
+                    }
                 }
             }
         }
@@ -565,196 +1732,200 @@ pub fn hard_subject_holes(timetable: &Timetable, shift: Shift, debug: bool) -> i
     points
 }
 
-pub fn hard_teacher_shift_spread(timetable: &Timetable, shift: Shift, debug: bool) -> i32 {
-    let mut points = 0;
-    /*
-    let teacher_spread: HashMap<&str, [i32; 5]> = HashMap::from([
-        ("Stanisic Milanka", [1, 1, 2, 2, 2]),
-        ("Knezevic Svetlana", [2, 2, 2, 1, 1]),
-        ("Scepanovic Suzana", [2, 2, 1, 1, 1]),
-        ("Becirovic Emsada", [1, 1, 1, 2, 2]),
-        ("Sukovic Biljana", [1, 1, 1, 2, 2]),
-        ("Scekic Jelena", [2, 1, 1, 1, 1]),
-        //
-        ("Mandic Olivera", [2, 2, 2, 3, 1]),
-        ("Ivanovic Olivera", [2, 2, 1, 1, 1]),
-        //
-        ("Papic Spasoje", [2, 1, 1, 2, 2]),
-        ("Zezelj Marija", [1, 2, 2, 2, 1]),
-        //
-        ("Sanja Radusinovic", [1, 2, 2, 1, 1]),
-        ("Jelena Bogicevic", [1, 1, 1, 2, 2]),
-        ("Ana Markovic", [2, 1, 1, 1, 2]),
-        ("Engleski 1", [1, 2, 1, 3, 1]),
-        //
-        ("Aleksandra Budrak", [2, 1, 2, 2, 2]),
-        ("Rada Mugosa", [2, 2, 2, 2, 1]),
-        ("Sociologija 2", [1, 1, 2, 2, 1]),
-        //
-        ("Cimbaljevic Drago", [2, 1, 1, 2, 2]),
-        ("Djeric Bogdan", [2, 2, 2, 1, 2]),
-        ("Fizicko 1", [2, 2, 2, 1, 1]),
-        ("Fizicko 2", [2, 2, 1, 1, 1]),
-        //
-        ("Zana Krgusic", [1, 1, 2, 2, 1]),
-        ("Dejan Maras", [2, 1, 1, 2, 1]),
-        ("Nevenka Roganovic", [1, 1, 2, 2, 2]),
-        ("Marija Babovic", [2, 1, 1, 2, 1]),
-        ("Natasa Stojanovic", [1, 1, 1, 2, 2]),
-        //
-        ("Samardzic Rada", [1, 2, 2, 1, 1]),
-        ("Vratnica Mladen", [1, 1, 2, 2, 2]),
-        //("Pekovic Milijana", [1, 2, 1, 2, 2]),
-        ("Zekovic Jelena", [1, 1, 2, 2, 2]),
-        ("Vojinovic Nikolija", [2, 2, 2, 1, 1]),
-        ("Dasic Nada", [1, 2, 2, 1, 2]),
-        ("Calasan Vesna", [1, 2, 2, 1, 1]),
-        ("Kocovic Mitra", [2, 1, 2, 1, 1]),
-        ("Energetika 1", [2, 2, 1, 1, 1]),
-        ("Energetika 2", [2, 1, 1, 1, 1]),
-        //
-        ("Djakovic Persa", [1, 1, 1, 1, 2]),
-        ("Matovic Dubravka", [2, 2, 1, 1, 2]),
-        ("Coguric Radmila", [1, 2, 2, 2, 1]),
-        ("Tasic Gordana", [2, 2, 1, 1, 2]),
-        ("Milentijevic Dragica", [1, 1, 2, 1, 1]),
-        ("Tadic Slobodan", [2, 2, 1, 1, 1]),
-        ("Nikolic Natalija", [2, 2, 2, 1, 2]),
-        ("Ana Vujovic", [2, 1, 1, 1, 2]),
-        ("Vemic Nada", [1, 2, 1, 2, 1]),
-        ("Radulovic Zoran", [2, 2, 2, 1, 1]),
-        ("Lucic Mileva", [2, 1, 2, 1, 1]),
-        ("Raskovic Violeta", [1, 2, 2, 1, 2]),
-        ("Kojovic Nikola", [2, 1, 2, 1, 2]),
-        ("Obradovic Aleksandar", [1, 2, 2, 1, 1]),
-        ("Stevovic Mirjana", [1, 2, 1, 2, 1]),
-        //
-        //("Lidija Vuletic", [1, 2, 2, 1, 2]),
-        //("Kovac Vladimir", [1, 2, 1, 2, 1]),
-        //("Babic Jelena", [1, 2, 1, 2, 2]),
-        //("Marina Radonjic", [2, 2, 1, 2, 2]),
-        //("Sladjana Saric", [1, 1, 2, 1, 1]),
-        //("Veselin Picuric", [2, 1, 2, 2, 1]),
-        //("Alen Adilovic", [1, 1, 1, 2, 2]),
-        //("Snezana Krunic", [2, 2, 1, 1, 2]),
-        //("Lopicic PRedrag", [1, 2, 1, 2, 1]),
-        //("Elektronika 1", [2, 2, 1, 1, 1]),
-        //("Elektronika 4", [2, 2, 2, 2, 1]),
-        //("Elektronika 6", [1, 1, 1, 2, 2]),
-        //("Elektronika 8", [2, 1, 1, 1, 2]),
-        //("Elektronika 9", [2, 2, 2, 2, 1]),
-    ]);
-
-    for day in 0..5 {
-        let mut seen_teachers: Vec<usize> = vec![];
+/// Increment points by the entry's weight for each lesson a teacher has during a period marked as unavailable (hard entries only)
+pub fn hard_teacher_unavailability(
+    timetable: &Timetable,
+    shift: Shift,
+    violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    teacher_unavailability(timetable, shift, true, violations)
+}
 
-        for class_slots in timetable.table(shift).iter() {
-            for period in 0..timetable.max_periods_per_day {
-                let index = day * timetable.max_periods_per_day + period;
+fn teacher_unavailability(
+    timetable: &Timetable,
+    shift: Shift,
+    hard: bool,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    if !timetable
+        .data
+        .teacher_unavailability
+        .iter()
+        .any(|u| u.hard == hard)
+    {
+        return 0;
+    }
 
-                match class_slots.slots[index as usize] {
-                    Slot::Single(s) => match s {
-                        SlotData::PartiallyFilled { teacher, .. } => {
-                            seen_teachers.push(teacher);
-                        }
+    (0..(timetable.max_periods_per_day * 5))
+        .map(|index| {
+            teacher_unavailability_at(timetable, shift, hard, index, violations.as_deref_mut())
+        })
+        .sum()
+}
 
-                        _ => {}
-                    },
-                    Slot::Double { first, second, .. } => {
-                        match first {
-                            SlotData::PartiallyFilled { teacher, .. } => {
-                                seen_teachers.push(teacher);
-                            }
+fn teacher_unavailability_at(
+    timetable: &Timetable,
+    shift: Shift,
+    hard: bool,
+    index: u32,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
 
-                            _ => {}
-                        }
+    let day = index / timetable.max_periods_per_day;
+    let period = index % timetable.max_periods_per_day;
 
-                        match second {
-                            SlotData::PartiallyFilled { teacher, .. } => {
-                                seen_teachers.push(teacher);
-                            }
+    for unavailability in timetable.data.teacher_unavailability.iter() {
+        if unavailability.hard != hard
+            || unavailability.day != day
+            || period < unavailability.first_period
+            || period > unavailability.last_period
+        {
+            continue;
+        }
 
-                            _ => {}
-                        }
+        for class_slots in timetable.table(shift).iter() {
+            let slot_datas = match class_slots.slots[index as usize] {
+                Slot::Single(s) => vec![s],
+                Slot::Double { first, second, .. } => vec![first, second],
+            };
+
+            for slot_data in slot_datas {
+                if let SlotData::PartiallyFilled { teacher, .. } = slot_data {
+                    if teacher == unavailability.teacher {
+                        points += unavailability.weight;
+
+                        record(
+                            &mut violations,
+                            unavailability.weight,
+                            Violation {
+                                class: Some(class_slots.class_index as usize),
+                                teacher: Some(teacher),
+                                ..Violation::at(timetable.max_periods_per_day, index)
+                            },
+                        );
                     }
                 }
             }
         }
+    }
 
-        for teacher in seen_teachers {
-            let name = timetable.data.teachers[teacher].name.as_str();
+    points
+}
 
-            if teacher_spread.contains_key(&name) {
-                let s = teacher_spread[&name][day as usize];
-                if s != shift.to_i32() && s != 3 {
-                    points += 1;
+/// Same as `soft_preferred_subject_times` except for the subject time rules marked as hard
+pub fn hard_subject_times(
+    timetable: &Timetable,
+    shift: Shift,
+    violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    subject_times(timetable, shift, true, violations)
+}
 
-                    if debug {
-                        let days = ["Mon", "Tue", "Wed", "Thu", "Fri"];
-                        println!("  {}, {}", name, days[day as usize]);
-                    }
-                }
-            }
+fn subject_times(
+    timetable: &Timetable,
+    shift: Shift,
+    hard: bool,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
+
+    let preferences = timetable.data.lookups().subject_times(hard);
+    if preferences.iter().all(|p| p.is_empty()) {
+        return 0;
+    }
+
+    for class_slots in timetable.table(shift).iter() {
+        for day in 0..5 {
+            points += class_day_subject_times(
+                timetable,
+                preferences,
+                class_slots,
+                day,
+                violations.as_deref_mut(),
+            );
         }
     }
-    */
+
     points
 }
 
-pub fn hard_teacher_extra_constraints(timetable: &Timetable, shift: Shift) -> i32 {
-    let mut points = 0;
+/// Indexed by subject
+fn subject_preferences(data: &TimetableData, hard: bool) -> Vec<Vec<SubjectTime>> {
+    let mut preferences: Vec<Vec<SubjectTime>> = vec![vec![]; data.subjects.len()];
+    for time in data.subject_times.iter() {
+        if time.hard == hard {
+            if let Some(p) = preferences.get_mut(time.subject) {
+                p.push(time.clone());
+            }
+        }
+    }
 
-    // 0 1 2 3 4 5 6    7 8 9 10 11 12 13    14 15 16 17 18 19 20    21 22 23 24 25 26 27    28 29 30 31 32 33 34
-    let blacklist: HashMap<usize, Vec<u32>> = HashMap::from([
-        (
-            59, // Svetlana Miranovic
-            vec![0, 1, 7, 8, 14, 15, 21, 23, 28, 29],
-        ),
-        (79, vec![5, 6, 12, 13, 19, 20, 26, 27]), // Lidija Lazarevic
-        (61, vec![5, 6, 19, 20, 33, 34]),         // Selman Sabotic
-        (33, vec![28, 29, 30, 31, 32, 33, 34]),   // Tadic Slobodan
-    ]);
+    preferences
+}
 
-    for class_slots in timetable.table(shift).iter() {
-        for period in 0..(timetable.max_periods_per_day * 5) {
-            match class_slots.slots[period as usize] {
-                Slot::Single(s) => match s {
-                    SlotData::PartiallyFilled { teacher, .. } => {
-                        if blacklist.contains_key(&teacher) {
-                            if blacklist[&teacher].contains(&period) {
-                                points += 1;
-                            }
-                        }
-                    }
+fn class_day_subject_times(
+    timetable: &Timetable,
+    preferences: &[Vec<SubjectTime>],
+    class_slots: &ClassSlots,
+    day: u32,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
 
-                    _ => {}
-                },
-                Slot::Double { first, second, .. } => {
-                    match first {
-                        SlotData::PartiallyFilled { teacher, .. } => {
-                            if blacklist.contains_key(&teacher) {
-                                if blacklist[&teacher].contains(&period) {
-                                    points += 1;
-                                }
-                            }
-                        }
+    // (period, subject, teacher) of every lesson during this day, including both groups
+    let mut lessons: Vec<(u32, usize, usize)> = vec![];
 
-                        _ => {}
-                    }
+    for period in 0..timetable.max_periods_per_day {
+        let index = day * timetable.max_periods_per_day + period;
 
-                    match second {
-                        SlotData::PartiallyFilled { teacher, .. } => {
-                            if blacklist.contains_key(&teacher) {
-                                if blacklist[&teacher].contains(&period) {
-                                    points += 1;
-                                }
-                            }
-                        }
+        match class_slots.slots[index as usize] {
+            Slot::Single(SlotData::PartiallyFilled {
+                teacher, subject, ..
+            }) => {
+                lessons.push((period, subject, teacher));
+            }
+            Slot::Double { first, second, .. } => {
+                if let SlotData::PartiallyFilled {
+                    teacher, subject, ..
+                } = first
+                {
+                    lessons.push((period, subject, teacher));
+                }
 
-                        _ => {}
-                    }
+                if let SlotData::PartiallyFilled {
+                    teacher, subject, ..
+                } = second
+                {
+                    lessons.push((period, subject, teacher));
                 }
             }
+
+            _ => {}
+        }
+    }
+
+    let first_period = lessons.iter().map(|l| l.0).min().unwrap_or(0);
+    let last_period = lessons.iter().map(|l| l.0).max().unwrap_or(0);
+
+    for (period, subject, teacher) in lessons {
+        for time in preferences[subject].iter() {
+            if time.is_broken_by(period, first_period, last_period) {
+                points += time.weight;
+
+                record(
+                    &mut violations,
+                    time.weight,
+                    Violation {
+                        class: Some(class_slots.class_index as usize),
+                        teacher: Some(teacher),
+                        ..Violation::at(
+                            timetable.max_periods_per_day,
+                            day * timetable.max_periods_per_day + period,
+                        )
+                    },
+                );
+            }
         }
     }
 
@@ -766,7 +1937,11 @@ pub fn hard_teacher_extra_constraints(timetable: &Timetable, shift: Shift) -> i3
 /* ==================== */
 
 /// Increment points by 1 for each day in a class timetable that contains more periods than what's ideal (even spread)
-pub fn soft_class_spread(timetable: &Timetable, shift: Shift) -> i32 {
+pub fn soft_class_spread(
+    timetable: &Timetable,
+    shift: Shift,
+    _violations: Option<&mut Vec<Violation>>,
+) -> i32 {
     let mut points = 0;
 
     /*
@@ -866,7 +2041,11 @@ pub fn soft_class_spread(timetable: &Timetable, shift: Shift) -> i32 {
 }
 
 /// Same as `soft_class_spread` except for teachers instead of classes
-pub fn soft_teacher_class_spread(timetable: &Timetable, teacher_table: &Vec<TeacherSlots>) -> i32 {
+pub fn soft_teacher_class_spread(
+    timetable: &Timetable,
+    teacher_table: &Vec<TeacherSlots>,
+    _violations: Option<&mut Vec<Violation>>,
+) -> i32 {
     let mut points = 0;
     /*
 
@@ -928,127 +2107,142 @@ pub fn soft_teacher_class_spread(timetable: &Timetable, teacher_table: &Vec<Teac
 pub fn soft_holes_in_teacher_timetable(
     timetable: &Timetable,
     teacher_table: &Vec<TeacherSlots>,
+    mut violations: Option<&mut Vec<Violation>>,
 ) -> i32 {
-    let mut points = 0;
-
-    for slots in teacher_table.iter() {
-        for day in 0..5 {
-            let mut start = 0;
-            for period in 0..timetable.max_periods_per_day {
-                let index = day * timetable.max_periods_per_day + period;
+    teacher_table
+        .iter()
+        .enumerate()
+        .map(|(teacher, slots)| teacher_holes(timetable, teacher, slots, violations.as_deref_mut()))
+        .sum()
+}
 
-                match slots.slots[index as usize] {
-                    TeacherSlot::Empty => {}
-                    _ => {
-                        start = period;
-                        break;
-                    }
-                }
-            }
+fn teacher_holes(
+    timetable: &Timetable,
+    teacher: usize,
+    slots: &TeacherSlots,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    let mut points = 0;
 
-            let mut end = 0;
-            for period in (0..timetable.max_periods_per_day).rev() {
-                let index = day * timetable.max_periods_per_day + period;
+    for day in 0..5 {
+        let mut start = 0;
+        for period in 0..timetable.max_periods_per_day {
+            let index = day * timetable.max_periods_per_day + period;
 
-                match slots.slots[index as usize] {
-                    TeacherSlot::Empty => {}
-                    _ => {
-                        end = period;
-                        break;
-                    }
+            match slots.slots[index as usize] {
+                TeacherSlot::Empty => {}
+                _ => {
+                    start = period;
+                    break;
                 }
             }
+        }
 
-            let mut holes = 0;
-            for period in start..end {
-                let index = day * timetable.max_periods_per_day + period;
+        let mut end = 0;
+        for period in (0..timetable.max_periods_per_day).rev() {
+            let index = day * timetable.max_periods_per_day + period;
 
-                match slots.slots[index as usize] {
-                    TeacherSlot::Empty => holes += 1,
-                    _ => {}
+            match slots.slots[index as usize] {
+                TeacherSlot::Empty => {}
+                _ => {
+                    end = period;
+                    break;
                 }
             }
-
-            if holes > 1 {
-                points += 1;
-            }
         }
-    }
-
-    points
-}
-
-pub fn soft_preferred_subject_times(timetable: &Timetable, shift: Shift) -> i32 {
-    let mut points = 0;
-    /*
-
-    for class_slots in timetable.table(shift).iter() {
-        for day in 0..5 {
-            // math shouldn't be the 6th or 7th class
-            let start = 5;
-            for period in start..timetable.max_periods_per_day {
-                let index = day * timetable.max_periods_per_day + period;
-
-                match class_slots.slots[index as usize] {
-                    Slot::Single(s) => match s {
-                        SlotData::PartiallyFilled { subject, .. } => {
-                            if subject == 67 {
-                                points += 1;
-                            }
-                        }
 
-                        _ => {}
-                    },
+        let mut holes = 0;
+        for period in start..end {
+            let index = day * timetable.max_periods_per_day + period;
 
-                    _ => {}
-                }
+            if let TeacherSlot::Empty = slots.slots[index as usize] {
+                holes += 1;
             }
+        }
 
-            // P.E. shouldn't be at the end of a day
-            /*
-            let period = timetable.max_periods_per_day as usize - 1;
-            match class_slots.slots
-                [day as usize * timetable.max_periods_per_day as usize + period as usize]
-            {
-                Slot::Single(s) => match s {
-                    SlotData::PartiallyFilled { subject, .. } => {
-                        if subject == 59 {
-                            points += 1;
-                        }
-                    }
+        if holes > 1 {
+            points += 1;
 
-                    _ => {}
+            record(
+                &mut violations,
+                1,
+                Violation {
+                    teacher: Some(teacher),
+                    ..Violation::on(day)
                 },
-
-                _ => {}
-            }
-            */
+            );
         }
     }
 
-    */
     points
 }
 
+/// Increment points by the preference's weight for each lesson held outside of its subject's preferred times (soft preferences only)
+pub fn soft_preferred_subject_times(
+    timetable: &Timetable,
+    shift: Shift,
+    violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    subject_times(timetable, shift, false, violations)
+}
+
+/// Same as `hard_teacher_unavailability` except for entries that aren't marked as hard
+pub fn soft_teacher_unavailability(
+    timetable: &Timetable,
+    shift: Shift,
+    violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    teacher_unavailability(timetable, shift, false, violations)
+}
+
+/// Increment points by 2 for each day a teacher has lessons in both shifts
 pub fn teacher_shifts(
     table1: &Vec<ClassSlots>,
     table2: &Vec<ClassSlots>,
     max_periods_per_day: u32,
-    data: &TimetableData,
-    debug: bool,
+    mut violations: Option<&mut Vec<Violation>>,
+) -> i32 {
+    (0..5)
+        .map(|day| {
+            teacher_shifts_on(
+                table1,
+                table2,
+                max_periods_per_day,
+                day,
+                violations.as_deref_mut(),
+            )
+        })
+        .sum()
+}
+
+/// Same as `teacher_shifts` except only for a single day
+pub fn teacher_shifts_on(
+    table1: &[ClassSlots],
+    table2: &[ClassSlots],
+    max_periods_per_day: u32,
+    day: u32,
+    mut violations: Option<&mut Vec<Violation>>,
 ) -> i32 {
     let mut points = 0;
 
-    for day in 0..5 {
-        let mut teachers_seen_in_shift1: HashMap<usize, u32> = HashMap::new();
-        let mut teachers_seen_in_shift2: HashMap<usize, u32> = HashMap::new();
+    let mut teachers_seen_in_shift1: HashMap<usize, u32> = HashMap::new();
+    let mut teachers_seen_in_shift2: HashMap<usize, u32> = HashMap::new();
 
-        for period in 0..max_periods_per_day {
-            let index = day * max_periods_per_day + period;
+    for period in 0..max_periods_per_day {
+        let index = day * max_periods_per_day + period;
 
-            for class_slots in table1.iter() {
-                match class_slots.slots[index as usize] {
-                    Slot::Single(SlotData::PartiallyFilled { teacher, .. }) => {
+        for class_slots in table1.iter() {
+            match class_slots.slots[index as usize] {
+                Slot::Single(SlotData::PartiallyFilled { teacher, .. }) => {
+                    teachers_seen_in_shift1
+                        .entry(teacher)
+                        .and_modify(|n| *n += 1)
+                        .or_insert(1);
+
+                    // teachers_seen_in_shift1.insert(teacher, true);
+                }
+                Slot::Double { first, second, .. } => {
+                    if let SlotData::PartiallyFilled { teacher, .. } = first {
                         teachers_seen_in_shift1
                             .entry(teacher)
                             .and_modify(|n| *n += 1)
@@ -1056,41 +2250,42 @@ pub fn teacher_shifts(
 
                         // teachers_seen_in_shift1.insert(teacher, true);
                     }
-                    Slot::Double { first, second, .. } => {
-                        match first {
-                            SlotData::PartiallyFilled { teacher, .. } => {
-                                teachers_seen_in_shift1
-                                    .entry(teacher)
-                                    .and_modify(|n| *n += 1)
-                                    .or_insert(1);
-
-                                // teachers_seen_in_shift1.insert(teacher, true);
-                            }
 
-                            _ => {}
-                        }
+                    if let SlotData::PartiallyFilled { teacher, .. } = second {
+                        teachers_seen_in_shift1
+                            .entry(teacher)
+                            .and_modify(|n| *n += 1)
+                            .or_insert(1);
 
-                        match second {
-                            SlotData::PartiallyFilled { teacher, .. } => {
-                                teachers_seen_in_shift1
-                                    .entry(teacher)
-                                    .and_modify(|n| *n += 1)
-                                    .or_insert(1);
+                        // teachers_seen_in_shift1.insert(teacher, true);
+                    }
+                }
 
-                                // teachers_seen_in_shift1.insert(teacher, true);
-                            }
+                _ => {}
+            }
+        }
 
-                            _ => {}
-                        }
-                    }
+        for class_slots in table2.iter() {
+            match class_slots.slots[index as usize] {
+                Slot::Single(SlotData::PartiallyFilled { teacher, .. }) => {
+                    teachers_seen_in_shift2
+                        .entry(teacher)
+                        .and_modify(|n| *n += 1)
+                        .or_insert(1);
 
-                    _ => {}
+                    // teachers_seen_in_shift2.insert(teacher, true);
                 }
-            }
+                Slot::Double { first, second, .. } => {
+                    if let SlotData::PartiallyFilled { teacher, .. } = first {
+                        teachers_seen_in_shift2
+                            .entry(teacher)
+                            .and_modify(|n| *n += 1)
+                            .or_insert(1);
 
-            for class_slots in table2.iter() {
-                match class_slots.slots[index as usize] {
-                    Slot::Single(SlotData::PartiallyFilled { teacher, .. }) => {
+                        // teachers_seen_in_shift2.insert(teacher, true);
+                    }
+
+                    if let SlotData::PartiallyFilled { teacher, .. } = second {
                         teachers_seen_in_shift2
                             .entry(teacher)
                             .and_modify(|n| *n += 1)
@@ -1098,59 +2293,226 @@ pub fn teacher_shifts(
 
                         // teachers_seen_in_shift2.insert(teacher, true);
                     }
-                    Slot::Double { first, second, .. } => {
-                        match first {
-                            SlotData::PartiallyFilled { teacher, .. } => {
-                                teachers_seen_in_shift2
-                                    .entry(teacher)
-                                    .and_modify(|n| *n += 1)
-                                    .or_insert(1);
+                }
 
-                                // teachers_seen_in_shift2.insert(teacher, true);
-                            }
+                _ => {}
+            }
+        }
+    }
 
-                            _ => {}
-                        }
+    for t in teachers_seen_in_shift1.keys() {
+        if teachers_seen_in_shift2.contains_key(t) {
+            points += 2;
 
-                        match second {
-                            SlotData::PartiallyFilled { teacher, .. } => {
-                                teachers_seen_in_shift2
-                                    .entry(teacher)
-                                    .and_modify(|n| *n += 1)
-                                    .or_insert(1);
+            record(
+                &mut violations,
+                2,
+                Violation {
+                    teacher: Some(*t),
+                    ..Violation::on(day)
+                },
+            );
+        }
+    }
 
-                                // teachers_seen_in_shift2.insert(teacher, true);
-                            }
+    points
+}
 
-                            _ => {}
-                        }
-                    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::TimetableData;
+    use crate::{Class, Subject, SubjectDays, Teacher};
+
+    const MAX_PERIODS_PER_DAY: u32 = 7;
+
+    /// A single class in the first shift with two subjects, taught by teachers 0 and 1
+    fn timetable(relations: Vec<Relation>) -> Timetable {
+        let data = TimetableData {
+            classes: vec![Class {
+                name: String::from("S1"),
+            }],
+            subjects: ["Matematika", "Fizika"]
+                .iter()
+                .map(|name| Subject {
+                    name: name.to_string(),
+                    kind: String::from("regular"),
+                    max_per_day: None,
+                })
+                .collect(),
+            teachers: ["A", "B"]
+                .iter()
+                .map(|name| Teacher {
+                    name: name.to_string(),
+                })
+                .collect(),
+            relations,
+            ..Default::default()
+        };
+
+        let empty = ClassSlots {
+            class_index: 0,
+            slots: vec![Slot::Single(SlotData::Empty); 5 * MAX_PERIODS_PER_DAY as usize],
+        };
+
+        Timetable::new(data, MAX_PERIODS_PER_DAY, vec![empty], vec![])
+    }
 
-                    _ => {}
-                }
-            }
+    fn relation(subject: usize, per_week: u32) -> Relation {
+        Relation {
+            shift: 1,
+            teacher: subject,
+            subject,
+            class: 0,
+            per_week_first: per_week,
+            per_week_second: None,
+            max_per_day: None,
+            blocks: vec![],
         }
+    }
 
-        for t in teachers_seen_in_shift1.keys() {
-            if teachers_seen_in_shift2.contains_key(t) {
-                points += 2;
+    /// Puts a lesson of the subject, taught by its teacher, into the class' slots
+    fn place(timetable: &mut Timetable, subject: usize, day: u32, periods: &[u32]) {
+        for period in periods {
+            timetable.table1[0].slots[(day * MAX_PERIODS_PER_DAY + period) as usize] =
+                Slot::Single(SlotData::PartiallyFilled {
+                    teacher: subject,
+                    subject,
+                    room: None,
+                });
+        }
+    }
 
-                if debug {
-                    let days = ["Mon", "Tue", "Wed", "Thu", "Fri"];
-                    println!(
-                        "  {} ({}) [{}]",
-                        data.teachers[*t].name,
-                        days[day as usize],
-                        teachers_seen_in_shift1[t] + teachers_seen_in_shift2[t]
-                    );
-                }
-            }
+    fn violations(
+        timetable: &Timetable,
+        evaluate: fn(&Timetable, Shift, Option<&mut Vec<Violation>>) -> i32,
+    ) -> (i32, Vec<Violation>) {
+        let mut violations: Vec<Violation> = vec![];
+        let points = evaluate(timetable, Shift::First, Some(&mut violations));
+
+        (points, violations)
+    }
+
+    #[test]
+    fn blocks_held_as_required() {
+        let mut timetable = timetable(vec![Relation {
+            blocks: vec![2, 1],
+            ..relation(0, 3)
+        }]);
+        place(&mut timetable, 0, 0, &[1, 2]);
+        place(&mut timetable, 0, 3, &[4]);
+
+        assert_eq!(violations(&timetable, hard_block_classes).0, 0);
+    }
+
+    #[test]
+    fn blocks_broken_by_lessons_split_across_days() {
+        let mut timetable = timetable(vec![Relation {
+            blocks: vec![2, 1],
+            ..relation(0, 3)
+        }]);
+        place(&mut timetable, 0, 0, &[1]);
+        place(&mut timetable, 0, 2, &[1]);
+        place(&mut timetable, 0, 3, &[4]);
+
+        let (points, violations) = violations(&timetable, hard_block_classes);
+        assert_eq!(points, 1);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].class, Some(0));
+        assert_eq!(violations[0].teacher, Some(0));
+    }
+
+    #[test]
+    fn relation_limit_overrides_subject_limit() {
+        let mut timetable = timetable(vec![
+            Relation {
+                max_per_day: Some(1),
+                ..relation(0, 2)
+            },
+            relation(1, 2),
+        ]);
+        timetable.data_mut().subjects[0].max_per_day = Some(2);
+        timetable.data_mut().subjects[1].max_per_day = Some(2);
+        place(&mut timetable, 0, 2, &[0, 1]);
+        place(&mut timetable, 1, 3, &[0, 1]);
+
+        let (points, violations) = violations(&timetable, hard_subject_per_day_limits);
+        assert_eq!(points, 1);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].class, Some(0));
+        assert_eq!(violations[0].day, Some(2));
+    }
+
+    #[test]
+    fn subject_limit_satisfied() {
+        let mut timetable = timetable(vec![relation(0, 2)]);
+        timetable.data_mut().subjects[0].max_per_day = Some(2);
+        place(&mut timetable, 0, 1, &[0, 1]);
+
+        assert_eq!(violations(&timetable, hard_subject_per_day_limits).0, 0);
+    }
+
+    #[test]
+    fn lookups_rebuilt_after_data_change() {
+        let mut timetable = timetable(vec![relation(0, 2)]);
+        place(&mut timetable, 0, 1, &[0, 1]);
+        assert_eq!(violations(&timetable, hard_subject_per_day_limits).0, 0);
+
+        timetable.data_mut().relations[0].max_per_day = Some(1);
+        assert_eq!(violations(&timetable, hard_subject_per_day_limits).0, 1);
+    }
+
+    fn subject_days(kind: SubjectDaysKind, days: u32) -> SubjectDays {
+        SubjectDays {
+            subjects: vec![0, 1],
+            classes: vec![0],
+            kind,
+            days,
+        }
+    }
+
+    #[test]
+    fn min_free_days_broken() {
+        let mut timetable = timetable(vec![relation(0, 3), relation(1, 2)]);
+        timetable.data_mut().subject_days = vec![subject_days(SubjectDaysKind::MinFreeDays, 2)];
+        for day in [0, 1, 3] {
+            place(&mut timetable, 0, day, &[0]);
         }
+        place(&mut timetable, 1, 4, &[2, 3]);
+
+        // held during 4 days, so only 1 is free
+        let (points, violations) = violations(&timetable, hard_specific_subject_days);
+        assert_eq!(points, 1);
+        assert_eq!(violations.iter().map(|v| v.severity).sum::<i32>(), 1);
+
+        let violation = violations.iter().find(|v| v.severity > 0).unwrap();
+        assert_eq!(violation.class, Some(0));
+        assert_eq!(violation.day, Some(4));
     }
 
-    if debug {
-        println!("\nTotal cost: {}", points);
+    #[test]
+    fn max_days_satisfied() {
+        let mut timetable = timetable(vec![relation(0, 3), relation(1, 2)]);
+        timetable.data_mut().subject_days = vec![subject_days(SubjectDaysKind::MaxDays, 2)];
+        place(&mut timetable, 0, 1, &[0, 1, 2]);
+        place(&mut timetable, 1, 3, &[0, 1]);
+
+        let (points, violations) = violations(&timetable, hard_specific_subject_days);
+        assert_eq!(points, 0);
+        assert!(violations.is_empty());
     }
 
-    points
+    #[test]
+    fn max_days_ignores_other_classes() {
+        let mut timetable = timetable(vec![relation(0, 3)]);
+        timetable.data_mut().subject_days = vec![SubjectDays {
+            classes: vec![1],
+            ..subject_days(SubjectDaysKind::MaxDays, 1)
+        }];
+        for day in [0, 2, 4] {
+            place(&mut timetable, 0, day, &[3]);
+        }
+
+        assert_eq!(violations(&timetable, hard_specific_subject_days).0, 0);
+    }
 }
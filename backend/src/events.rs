@@ -0,0 +1,100 @@
+use serde::Serialize;
+
+use crate::{
+    cost::{CostBreakdown, CostReport},
+    logic::{AnnealingParams, AnnealingResult, ClassSlots, Escape, Timetable},
+};
+
+/// How a run is going, reported every `progress_ms`
+#[derive(Clone, Copy, Serialize, Debug)]
+pub struct Progress {
+    pub seed: u64,
+    pub temperature: f64,
+    pub iteration: u64,
+    pub current: CostBreakdown,
+    pub best: CostBreakdown,
+    pub acceptance_rate: f64, // share of the iterations since the last report that accepted a candidate
+    pub elapsed_secs: f64,
+}
+
+/// Receives what the solver reports while it runs, so the solver itself doesn't care where it goes (socket, terminal, nowhere)
+pub trait SolverEvents {
+    fn started(&mut self, _params: &AnnealingParams) {}
+
+    /// A candidate was accepted, `best` is the cost of the best solution seen so far
+    fn accepted(&mut self, _temperature: f64, _current: &CostBreakdown, _best: &CostBreakdown) {}
+
+    fn progress(&mut self, _progress: &Progress) {}
+
+    /// The search got stuck and was reheated or restarted, `current` is the cost it goes on from
+    fn escaped(&mut self, _escape: Escape, _temperature: f64, _current: &CostBreakdown) {}
+
+    /// The best solution changed, sent at most every `snapshot_ms`
+    fn best_snapshot(
+        &mut self,
+        _table1: &[ClassSlots],
+        _table2: &[ClassSlots],
+        _cost: &CostBreakdown,
+    ) {
+    }
+
+    /// The timetable now holds the best solution, which `report` describes
+    fn finished(
+        &mut self,
+        _result: &AnnealingResult,
+        _timetable: &Timetable,
+        _report: &CostReport,
+    ) {
+    }
+}
+
+/// Drops every event
+pub struct NoEvents;
+
+impl SolverEvents for NoEvents {}
+
+/// Prints every event in the log format the server has always used
+pub struct LogEvents;
+
+impl SolverEvents for LogEvents {
+    fn started(&mut self, params: &AnnealingParams) {
+        println!("Seed: {}", params.seed);
+    }
+
+    fn accepted(&mut self, temperature: f64, current: &CostBreakdown, best: &CostBreakdown) {
+        println!(
+            "[TEMP: {}] {}    (best: {})",
+            temperature,
+            current,
+            best.total(),
+        );
+    }
+
+    fn progress(&mut self, progress: &Progress) {
+        println!(
+            "[PROGRESS: {:.1}s] iteration {}, T {:.4}, accepted {:.1}%, current {}, best {}",
+            progress.elapsed_secs,
+            progress.iteration,
+            progress.temperature,
+            progress.acceptance_rate * 100.0,
+            progress.current.total(),
+            progress.best.total(),
+        );
+    }
+
+    fn escaped(&mut self, escape: Escape, temperature: f64, current: &CostBreakdown) {
+        println!(
+            "[ESCAPE: {:?}] T {}, current {}",
+            escape, temperature, current
+        );
+    }
+
+    fn finished(&mut self, result: &AnnealingResult, _timetable: &Timetable, _report: &CostReport) {
+        println!(
+            "Finished: {:?} after {} iterations",
+            result.reason, result.iterations
+        );
+        println!("Current: {}", result.current);
+        println!("Best:    {}", result.best);
+    }
+}
@@ -0,0 +1,585 @@
+use std::{fs, path::Path};
+
+use csv::Reader;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::logic::{ClassSlots, Timetable, TimetableData};
+use crate::*;
+
+/// Tabs that are imported from csv files, in an order where every tab comes after the ones its names refer to
+pub const TABS: [&str; 10] = [
+    "classes",
+    "rooms",
+    "subjects",
+    "teachers",
+    "relations",
+    "teacher_availability",
+    "teacher_unavailability",
+    "room_rules",
+    "subject_times",
+    "subject_days",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoomRecord {
+    pub name: String,
+    pub kinds: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TeacherAvailabilityRecord {
+    pub teacher: String,
+    pub mon: DayAvailability,
+    pub tue: DayAvailability,
+    pub wed: DayAvailability,
+    pub thu: DayAvailability,
+    pub fri: DayAvailability,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TeacherUnavailabilityRecord {
+    pub teacher: String,
+    pub day: u32,
+    pub first_period: u32,
+    pub last_period: u32,
+    pub hard: bool,
+    pub weight: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoomRuleRecord {
+    pub teacher: Option<String>,
+    pub class: Option<String>,
+    pub kinds: String,
+    pub rooms: String,
+    pub priority: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubjectTimeRecord {
+    pub subject: String,
+    pub kind: SubjectTimeKind,
+    pub periods: String,
+    pub hard: bool,
+    pub weight: i32,
+}
+
+// subjects and classes are separated by semicolons since subject names can contain spaces
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubjectDaysRecord {
+    pub subjects: String,
+    pub classes: String,
+    pub kind: SubjectDaysKind,
+    pub days: u32,
+}
+
+/// Appends the rows of `<dir>/<tab>.csv` to the timetable's data, failing on the first name that doesn't exist
+pub fn import_tab(timetable: &mut Timetable, dir: &Path, tab: &str) -> Result<(), String> {
+    let mut rdr = Reader::from_path(dir.join(format!("{}.csv", tab))).unwrap();
+
+    match tab {
+        "classes" => {
+            for result in rdr.deserialize() {
+                let record: Class = result.unwrap();
+                timetable.data_mut().classes.push(record);
+            }
+        }
+        "rooms" => {
+            for result in rdr.deserialize() {
+                let record: RoomRecord = result.unwrap();
+
+                let room: Room = Room {
+                    name: record.name,
+                    kinds: record.kinds.split(' ').map(str::to_string).collect(),
+                };
+                timetable.data_mut().rooms.push(room);
+            }
+        }
+        "subjects" => {
+            for result in rdr.deserialize() {
+                let record: Subject = result.unwrap();
+                timetable.data_mut().subjects.push(record);
+            }
+        }
+        "teachers" => {
+            for result in rdr.deserialize() {
+                let record: Teacher = result.unwrap();
+                timetable.data_mut().teachers.push(record);
+            }
+        }
+        "relations" => {
+            for result in rdr.deserialize() {
+                let record: Relation = result.unwrap();
+                timetable.data_mut().relations.push(record);
+            }
+        }
+        "teacher_availability" => {
+            for result in rdr.deserialize() {
+                let record: TeacherAvailabilityRecord = result.unwrap();
+
+                let days = [record.mon, record.tue, record.wed, record.thu, record.fri];
+
+                match timetable.data.teacher_index(&record.teacher) {
+                    Some(teacher) => timetable
+                        .data_mut()
+                        .teacher_availability
+                        .push(TeacherAvailability { teacher, days }),
+                    None => return Err(format!("Unknown teacher: {}", record.teacher)),
+                }
+            }
+        }
+        "teacher_unavailability" => {
+            for result in rdr.deserialize() {
+                let record: TeacherUnavailabilityRecord = result.unwrap();
+
+                match timetable.data.teacher_index(&record.teacher) {
+                    Some(teacher) => {
+                        timetable
+                            .data_mut()
+                            .teacher_unavailability
+                            .push(TeacherUnavailability {
+                                teacher,
+                                day: record.day,
+                                first_period: record.first_period,
+                                last_period: record.last_period,
+                                hard: record.hard,
+                                weight: record.weight,
+                            })
+                    }
+                    None => return Err(format!("Unknown teacher: {}", record.teacher)),
+                }
+            }
+        }
+        "room_rules" => {
+            for result in rdr.deserialize() {
+                let record: RoomRuleRecord = result.unwrap();
+
+                match room_rule_from_record(&timetable.data, &record) {
+                    Ok(rule) => timetable.data_mut().room_rules.push(rule),
+                    Err(name) => return Err(format!("Unknown name in room rule: {}", name)),
+                }
+            }
+        }
+        "subject_times" => {
+            for result in rdr.deserialize() {
+                let record: SubjectTimeRecord = result.unwrap();
+
+                let subject_time = subject_time_from_record(&timetable.data, &record)?;
+                timetable.data_mut().subject_times.push(subject_time);
+            }
+        }
+        "subject_days" => {
+            for result in rdr.deserialize() {
+                let record: SubjectDaysRecord = result.unwrap();
+
+                match subject_days_from_record(&timetable.data, &record) {
+                    Ok(constraint) => timetable.data_mut().subject_days.push(constraint),
+                    Err(name) => return Err(format!("Unknown name in subject days: {}", name)),
+                }
+            }
+        }
+
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Replaces both tables (and the seed) with the ones in an exported timetable.json, leaving them untouched if it doesn't fit
+pub fn import_timetable(timetable: &mut Timetable, path: &Path) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let json: Value = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+
+    let table1 = imported_table(&json, "table1", timetable.table1.len())?;
+    let table2 = imported_table(&json, "table2", timetable.table2.len())?;
+
+    for (class_slots, imported) in timetable.table1.iter_mut().zip(table1) {
+        *class_slots = imported;
+    }
+    for (class_slots, imported) in timetable.table2.iter_mut().zip(table2) {
+        *class_slots = imported;
+    }
+    timetable.seed = json["seed"].as_u64();
+
+    Ok(())
+}
+
+fn imported_table(json: &Value, key: &str, classes: usize) -> Result<Vec<ClassSlots>, String> {
+    let table: Vec<ClassSlots> =
+        serde_json::from_value(json[key].clone()).map_err(|e| format!("{}: {}", key, e))?;
+    if table.len() != classes {
+        return Err(format!(
+            "{} has {} classes, expected {}",
+            key,
+            table.len(),
+            classes
+        ));
+    }
+
+    Ok(table)
+}
+
+/// Contents of an exported timetable.json
+pub fn timetable_json(timetable: &Timetable) -> Value {
+    json!({
+        "table1": timetable.table1,
+        "table2": timetable.table2,
+        "seed": timetable.seed,
+    })
+}
+
+/// Resolves the names in a room rule record to indices, returning the first name that doesn't exist
+pub fn room_rule_from_record(
+    data: &TimetableData,
+    record: &RoomRuleRecord,
+) -> Result<RoomRule, String> {
+    let teacher = match &record.teacher {
+        Some(name) => Some(data.teacher_index(name).ok_or(name.clone())?),
+        None => None,
+    };
+    let class = match &record.class {
+        Some(name) => Some(data.class_index(name).ok_or(name.clone())?),
+        None => None,
+    };
+
+    let mut rooms: Vec<usize> = vec![];
+    for name in record.rooms.split_whitespace() {
+        let indices = data.room_indices(name);
+        if indices.is_empty() {
+            return Err(name.to_string());
+        }
+        rooms.extend(indices);
+    }
+
+    Ok(RoomRule {
+        teacher,
+        class,
+        kinds: record
+            .kinds
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        rooms,
+        priority: record.priority,
+    })
+}
+
+/// Resolves the subject of a subject time record and parses its periods
+pub fn subject_time_from_record(
+    data: &TimetableData,
+    record: &SubjectTimeRecord,
+) -> Result<SubjectTime, String> {
+    let subject = data
+        .subject_index(&record.subject)
+        .ok_or(format!("Unknown subject: {}", record.subject))?;
+
+    let mut periods: Vec<u32> = vec![];
+    for period in record.periods.split_whitespace() {
+        periods.push(
+            period
+                .parse()
+                .map_err(|_| format!("Invalid period of {}: {}", record.subject, period))?,
+        );
+    }
+
+    Ok(SubjectTime {
+        subject,
+        kind: record.kind,
+        periods,
+        hard: record.hard,
+        weight: record.weight,
+    })
+}
+
+/// Resolves the names in a subject days record to indices, returning the first name that doesn't exist
+pub fn subject_days_from_record(
+    data: &TimetableData,
+    record: &SubjectDaysRecord,
+) -> Result<SubjectDays, String> {
+    let mut subjects: Vec<usize> = vec![];
+    for name in record.subjects.split(';').map(str::trim) {
+        subjects.push(data.subject_index(name).ok_or(name.to_string())?);
+    }
+
+    let mut classes: Vec<usize> = vec![];
+    for name in record.classes.split(';').map(str::trim) {
+        classes.push(data.class_index(name).ok_or(name.to_string())?);
+    }
+
+    Ok(SubjectDays {
+        subjects,
+        classes,
+        kind: record.kind,
+        days: record.days,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+
+    fn data() -> TimetableData {
+        TimetableData {
+            classes: ["S4A", "S4B", "S2A"]
+                .iter()
+                .map(|name| Class {
+                    name: name.to_string(),
+                })
+                .collect(),
+            subjects: ["Matematika", "Fizicko", "Engleski jezik"]
+                .iter()
+                .map(|name| Subject {
+                    name: name.to_string(),
+                    kind: String::from("regular"),
+                    max_per_day: None,
+                })
+                .collect(),
+            rooms: ["1", "Sala", "2", "Sala"]
+                .iter()
+                .map(|name| Room {
+                    name: name.to_string(),
+                    kinds: vec![String::from("regular")],
+                })
+                .collect(),
+            teachers: ["Stanisic Milanka", "Dejan Maras"]
+                .iter()
+                .map(|name| Teacher {
+                    name: name.to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn records<T: DeserializeOwned>(csv: &str) -> Vec<T> {
+        Reader::from_reader(csv.as_bytes())
+            .deserialize()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    /// Imports `csv` as the tab into a timetable holding `data()`
+    fn import(tab: &str, csv: &str) -> (Timetable, Result<(), String>) {
+        // tests run in parallel, so each import gets a directory of its own
+        static IMPORTS: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "tomoyo-{}-{}",
+            std::process::id(),
+            IMPORTS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("{}.csv", tab)), csv).unwrap();
+
+        let mut timetable = Timetable::new(data(), 7, vec![], vec![]);
+        let result = import_tab(&mut timetable, &dir, tab);
+        fs::remove_dir_all(&dir).unwrap();
+
+        (timetable, result)
+    }
+
+    #[test]
+    fn teacher_availability_records() {
+        let (timetable, result) = import(
+            "teacher_availability",
+            "teacher,mon,tue,wed,thu,fri
+Dejan Maras,1,2,both,off,1
+",
+        );
+        result.unwrap();
+
+        let availability = &timetable.data.teacher_availability;
+        assert_eq!(availability.len(), 1);
+        assert_eq!(availability[0].teacher, 1);
+        assert_eq!(
+            availability[0].days,
+            [
+                DayAvailability::First,
+                DayAvailability::Second,
+                DayAvailability::Both,
+                DayAvailability::Off,
+                DayAvailability::First,
+            ]
+        );
+    }
+
+    #[test]
+    fn teacher_availability_record_with_unknown_teacher() {
+        let (timetable, result) = import(
+            "teacher_availability",
+            "teacher,mon,tue,wed,thu,fri
+Dejan Marras,1,1,1,1,1
+",
+        );
+
+        assert_eq!(result.unwrap_err(), "Unknown teacher: Dejan Marras");
+        assert!(timetable.data.teacher_availability.is_empty());
+    }
+
+    #[test]
+    fn subject_days_records() {
+        let records = records(
+            "subjects,classes,kind,days
+Matematika;Engleski jezik,S4A; S2A,min_free_days,1
+Fizicko,S4B,max_days,3
+",
+        );
+
+        let min_free = subject_days_from_record(&data(), &records[0]).unwrap();
+        assert_eq!(min_free.subjects, vec![0, 2]);
+        assert_eq!(min_free.classes, vec![0, 2]);
+        assert_eq!(min_free.kind, SubjectDaysKind::MinFreeDays);
+        assert_eq!(min_free.days, 1);
+
+        let max_days = subject_days_from_record(&data(), &records[1]).unwrap();
+        assert_eq!(max_days.subjects, vec![1]);
+        assert_eq!(max_days.classes, vec![1]);
+        assert_eq!(max_days.kind, SubjectDaysKind::MaxDays);
+        assert_eq!(max_days.days, 3);
+    }
+
+    #[test]
+    fn subject_days_record_with_unknown_name() {
+        let records = records(
+            "subjects,classes,kind,days
+Matematika;Hemija,S4A,max_days,2
+",
+        );
+
+        assert_eq!(
+            subject_days_from_record(&data(), &records[0]).unwrap_err(),
+            "Hemija"
+        );
+    }
+
+    #[test]
+    fn teacher_unavailability_records() {
+        let (timetable, result) = import(
+            "teacher_unavailability",
+            "teacher,day,first_period,last_period,hard,weight
+Stanisic Milanka,2,0,3,true,1
+Dejan Maras,4,5,6,false,3
+",
+        );
+        result.unwrap();
+
+        let unavailability = &timetable.data.teacher_unavailability;
+        assert_eq!(unavailability.len(), 2);
+
+        assert_eq!(unavailability[0].teacher, 0);
+        assert_eq!(unavailability[0].day, 2);
+        assert_eq!(unavailability[0].first_period, 0);
+        assert_eq!(unavailability[0].last_period, 3);
+        assert!(unavailability[0].hard);
+        assert_eq!(unavailability[0].weight, 1);
+
+        assert_eq!(unavailability[1].teacher, 1);
+        assert_eq!(unavailability[1].day, 4);
+        assert_eq!(unavailability[1].first_period, 5);
+        assert_eq!(unavailability[1].last_period, 6);
+        assert!(!unavailability[1].hard);
+        assert_eq!(unavailability[1].weight, 3);
+    }
+
+    #[test]
+    fn teacher_unavailability_record_with_unknown_teacher() {
+        let (_, result) = import(
+            "teacher_unavailability",
+            "teacher,day,first_period,last_period,hard,weight
+Stanisic Milanka,2,0,3,true,1
+Selman Sabotic,0,0,1,true,1
+",
+        );
+
+        assert_eq!(result.unwrap_err(), "Unknown teacher: Selman Sabotic");
+    }
+
+    #[test]
+    fn room_rule_records() {
+        let records = records(
+            "teacher,class,kinds,rooms,priority
+Dejan Maras,,computer masinska-computer,2 Sala,1
+,S4B,,1,2
+",
+        );
+
+        let teacher_rule = room_rule_from_record(&data(), &records[0]).unwrap();
+        assert_eq!(teacher_rule.teacher, Some(1));
+        assert_eq!(teacher_rule.class, None);
+        assert_eq!(teacher_rule.kinds, vec!["computer", "masinska-computer"]);
+        // every room sharing a name is included
+        assert_eq!(teacher_rule.rooms, vec![2, 1, 3]);
+        assert_eq!(teacher_rule.priority, 1);
+
+        let class_rule = room_rule_from_record(&data(), &records[1]).unwrap();
+        assert_eq!(class_rule.teacher, None);
+        assert_eq!(class_rule.class, Some(1));
+        assert!(class_rule.kinds.is_empty());
+        assert_eq!(class_rule.rooms, vec![0]);
+        assert_eq!(class_rule.priority, 2);
+    }
+
+    #[test]
+    fn room_rule_record_with_unknown_name() {
+        let records: Vec<RoomRuleRecord> = records(
+            "teacher,class,kinds,rooms,priority
+,S4A,,1 Sala1,1
+,S9Z,,1,1
+Dejan Marras,,,1,1
+",
+        );
+
+        for (record, name) in records.iter().zip(["Sala1", "S9Z", "Dejan Marras"]) {
+            assert_eq!(room_rule_from_record(&data(), record).unwrap_err(), name);
+        }
+    }
+
+    #[test]
+    fn subject_time_records() {
+        let records = records(
+            "subject,kind,periods,hard,weight
+Matematika,allowed,0 1  2 3,false,2
+Fizicko,not_last,,true,1
+",
+        );
+
+        let allowed = subject_time_from_record(&data(), &records[0]).unwrap();
+        assert_eq!(allowed.subject, 0);
+        assert_eq!(allowed.kind, SubjectTimeKind::Allowed);
+        assert_eq!(allowed.periods, vec![0, 1, 2, 3]);
+        assert!(!allowed.hard);
+        assert_eq!(allowed.weight, 2);
+
+        let not_last = subject_time_from_record(&data(), &records[1]).unwrap();
+        assert_eq!(not_last.subject, 1);
+        assert_eq!(not_last.kind, SubjectTimeKind::NotLast);
+        assert!(not_last.periods.is_empty());
+        assert!(not_last.hard);
+    }
+
+    #[test]
+    fn subject_time_record_with_bad_periods() {
+        let records: Vec<SubjectTimeRecord> = records(
+            "subject,kind,periods,hard,weight
+Matematika,forbidden,0 1;2,true,1
+Matematika,forbidden,0 -1,true,1
+Hemija,forbidden,0,true,1
+",
+        );
+
+        for (record, error) in records.iter().zip([
+            "Invalid period of Matematika: 1;2",
+            "Invalid period of Matematika: -1",
+            "Unknown subject: Hemija",
+        ]) {
+            assert_eq!(
+                subject_time_from_record(&data(), record).unwrap_err(),
+                error
+            );
+        }
+    }
+}
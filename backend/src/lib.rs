@@ -1,3 +1,10 @@
+pub mod cost;
+pub mod events;
+pub mod files;
+pub mod logic;
+pub mod regular_tabs;
+pub mod util;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -15,6 +22,8 @@ pub struct Room {
 pub struct Subject {
     pub name: String,
     pub kind: String,
+    #[serde(default)]
+    pub max_per_day: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -30,4 +39,175 @@ pub struct Relation {
     pub class: usize,
     pub per_week_first: u32,
     pub per_week_second: Option<u32>,
+    #[serde(default)]
+    pub max_per_day: Option<u32>, // overrides the subject's limit for this class
+    #[serde(default, with = "block_lengths")]
+    pub blocks: Vec<u32>, // lengths of the consecutive blocks lessons must be held in (per group), e.g. 2+1
+}
+
+/// (De)serializes block lengths as a single "2+1" string, so that they fit into one CSV column
+mod block_lengths {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(blocks: &[u32], serializer: S) -> Result<S::Ok, S::Error> {
+        let blocks: Vec<String> = blocks.iter().map(|b| b.to_string()).collect();
+        serializer.serialize_str(&blocks.join("+"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u32>, D::Error> {
+        let blocks = String::deserialize(deserializer)?;
+
+        blocks
+            .split('+')
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+            .map(|b| b.parse().map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+/// Which shift(s) a teacher is able to work on a single day
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DayAvailability {
+    #[serde(rename = "off")]
+    Off,
+    #[serde(rename = "1")]
+    First,
+    #[serde(rename = "2")]
+    Second,
+    #[serde(rename = "both")]
+    Both,
+}
+
+impl DayAvailability {
+    pub fn allows_shift(&self, shift: i32) -> bool {
+        match self {
+            DayAvailability::Off => false,
+            DayAvailability::First => shift == 1,
+            DayAvailability::Second => shift == 2,
+            DayAvailability::Both => true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TeacherAvailability {
+    pub teacher: usize,
+    pub days: [DayAvailability; 5], // mon, tue, wed, thu, fri
+}
+
+/// A range of periods during which a teacher shouldn't be teaching
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TeacherUnavailability {
+    pub teacher: usize,
+    pub day: u32,          // 0 - 4 (mon - fri)
+    pub first_period: u32, // 0-based, inclusive
+    pub last_period: u32,  // 0-based, inclusive
+    pub hard: bool,
+    pub weight: i32,
+}
+
+/// Pins lessons matching all of the set conditions to one of the given rooms
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoomRule {
+    pub teacher: Option<usize>,
+    pub class: Option<usize>,
+    pub kinds: Vec<String>, // subject kinds, matches any kind if empty
+    pub rooms: Vec<usize>,  // tried in order, the first one that's free is used
+    pub priority: i32,      // the matching rule with the highest priority wins
+}
+
+impl RoomRule {
+    pub fn matches(&self, class: usize, teacher: usize, kind: &str) -> bool {
+        self.class.is_none_or(|c| c == class)
+            && self.teacher.is_none_or(|t| t == teacher)
+            && (self.kinds.is_empty() || self.kinds.iter().any(|k| k == kind))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubjectTimeKind {
+    Allowed,   // lessons are held only during the given periods
+    Forbidden, // lessons aren't held during the given periods
+    NotFirst,  // lessons aren't the first one of the day
+    NotLast,   // lessons aren't the last one of the day
+}
+
+/// A time preference for all lessons of a subject
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubjectTime {
+    pub subject: usize,
+    pub kind: SubjectTimeKind,
+    pub periods: Vec<u32>, // 0-based periods of the day, unused by `NotFirst` and `NotLast`
+    pub hard: bool,
+    pub weight: i32,
+}
+
+impl SubjectTime {
+    /// Whether a lesson held during `period` breaks this preference, given the first and last period of the class' day
+    pub fn is_broken_by(&self, period: u32, first_period: u32, last_period: u32) -> bool {
+        match self.kind {
+            SubjectTimeKind::Allowed => !self.periods.contains(&period),
+            SubjectTimeKind::Forbidden => self.periods.contains(&period),
+            SubjectTimeKind::NotFirst => period == first_period,
+            SubjectTimeKind::NotLast => period == last_period,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubjectDaysKind {
+    MinFreeDays, // at least `days` days without any of the subjects
+    MaxDays,     // the subjects are held during at most `days` days
+}
+
+/// Limits the number of days during which a group of subjects is held, for each of the given classes
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubjectDays {
+    pub subjects: Vec<usize>,
+    pub classes: Vec<usize>,
+    pub kind: SubjectDaysKind,
+    pub days: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relations(csv: &str) -> Vec<Relation> {
+        csv::Reader::from_reader(csv.as_bytes())
+            .deserialize()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn relation_limit_and_blocks_columns() {
+        let relations = relations(
+            "shift,teacher,subject,class,per_week_first,per_week_second,max_per_day,blocks
+1,0,0,0,3,,2,2+1
+2,1,1,0,2,2,,
+",
+        );
+
+        assert_eq!(relations[0].max_per_day, Some(2));
+        assert_eq!(relations[0].blocks, vec![2, 1]);
+        assert_eq!(relations[1].per_week_second, Some(2));
+        assert_eq!(relations[1].max_per_day, None);
+        assert!(relations[1].blocks.is_empty());
+    }
+
+    #[test]
+    fn relation_without_limit_and_blocks_columns() {
+        let relations = relations(
+            "shift,teacher,subject,class,per_week_first,per_week_second
+1,7,29,6,1,
+",
+        );
+
+        assert_eq!(relations[0].max_per_day, None);
+        assert!(relations[0].blocks.is_empty());
+    }
 }
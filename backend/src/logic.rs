@@ -1,20 +1,29 @@
 use std::{
+    collections::VecDeque,
     println,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex, OnceLock,
     },
+    thread,
+    time::Instant,
     unreachable, vec,
 };
 
-use project_tomoyo::*;
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
-use ws::Sender;
+use serde_json::Value;
 
-use crate::{cost, util};
+use crate::*;
+use crate::{
+    cost::{
+        self, constraint_report, ConstraintRegistry, CostBreakdown, CostReport, DataLookups,
+        ScoreUpdate, ShiftReport, ShiftScores, ShiftView, TeacherShiftsScores, Weights,
+    },
+    events::{NoEvents, Progress, SolverEvents},
+};
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
 pub enum SlotData {
     Empty,
     PartiallyFilled {
@@ -24,7 +33,7 @@ pub enum SlotData {
     },
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
 pub enum Slot {
     Single(SlotData),
     Double {
@@ -35,13 +44,93 @@ pub enum Slot {
     },
 }
 
-#[derive(Clone, Serialize, Debug)]
+#[derive(Clone, Serialize, Debug, Default)]
 pub struct TimetableData {
     pub classes: Vec<Class>,
     pub rooms: Vec<Room>,
     pub subjects: Vec<Subject>,
     pub teachers: Vec<Teacher>,
     pub relations: Vec<Relation>,
+    pub teacher_availability: Vec<TeacherAvailability>,
+    pub teacher_unavailability: Vec<TeacherUnavailability>,
+    pub room_rules: Vec<RoomRule>,
+    pub subject_times: Vec<SubjectTime>,
+    pub subject_days: Vec<SubjectDays>,
+    #[serde(skip)]
+    pub(crate) lookups: OnceLock<DataLookups>, // dropped by `Timetable::data_mut`
+}
+
+impl TimetableData {
+    pub fn teacher_index(&self, name: &str) -> Option<usize> {
+        self.teachers
+            .iter()
+            .position(|teacher| teacher.name == name)
+    }
+
+    pub fn subject_index(&self, name: &str) -> Option<usize> {
+        self.subjects
+            .iter()
+            .position(|subject| subject.name == name)
+    }
+
+    pub fn class_index(&self, name: &str) -> Option<usize> {
+        self.classes.iter().position(|class| class.name == name)
+    }
+
+    /// Built on first use after every change to the data
+    pub fn lookups(&self) -> &DataLookups {
+        self.lookups.get_or_init(|| DataLookups::new(self))
+    }
+
+    /// All rooms with the given name, since multiple rooms can share one
+    pub fn room_indices(&self, name: &str) -> Vec<usize> {
+        (0..self.rooms.len())
+            .filter(|i| self.rooms[*i].name == name)
+            .collect()
+    }
+
+    /// All subject and room kinds, ordered from the scarcest (supported by the fewest rooms) to the most common one.
+    ///
+    /// Rooms are handed out to kinds in this order, so that flexible kinds don't take the only rooms a scarce kind can use.
+    pub fn kinds_by_scarcity(&self) -> Vec<String> {
+        let mut kinds: Vec<String> = vec![];
+        for kind in self
+            .subjects
+            .iter()
+            .map(|subject| &subject.kind)
+            .chain(self.rooms.iter().flat_map(|room| room.kinds.iter()))
+        {
+            if !kinds.contains(kind) {
+                kinds.push(kind.clone());
+            }
+        }
+
+        let room_count = |kind: &String| {
+            self.rooms
+                .iter()
+                .filter(|room| room.kinds.contains(kind))
+                .count()
+        };
+
+        kinds.sort_by(|a, b| room_count(a).cmp(&room_count(b)).then(a.cmp(b)));
+
+        kinds
+    }
+
+    /// The highest priority room rule matching the lesson, if any
+    pub fn room_rule(&self, class: usize, teacher: usize, subject: usize) -> Option<&RoomRule> {
+        let mut best: Option<&RoomRule> = None;
+
+        for rule in self.room_rules.iter() {
+            if rule.matches(class, teacher, &self.subjects[subject].kind)
+                && best.is_none_or(|b| rule.priority > b.priority)
+            {
+                best = Some(rule);
+            }
+        }
+
+        best
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -50,7 +139,654 @@ pub struct ClassSlots {
     pub slots: Vec<Slot>,
 }
 
-#[derive(Clone, Copy, PartialEq, PartialOrd)]
+/// Solution the annealer is working on along with the cost of every part of it, so only candidates have to be scored
+pub struct ScoredSolution {
+    pub timetable: Timetable,
+    scores1: ShiftScores,
+    scores2: ShiftScores,
+    shifts_scores: TeacherShiftsScores,
+    pub cost: CostBreakdown,
+}
+
+/// Move applied to a single shift of a scored solution, along with its scores
+pub struct Candidate {
+    pub mv: Move,
+    pub update: ScoreUpdate,
+}
+
+impl ScoredSolution {
+    pub fn new(timetable: &Timetable, weights: &Weights) -> Self {
+        let registry = &timetable.constraints;
+
+        let mut solution = Self {
+            timetable: timetable.clone(),
+            scores1: ShiftScores::new(registry, &ShiftView::new(timetable, Shift::First), weights),
+            scores2: ShiftScores::new(registry, &ShiftView::new(timetable, Shift::Second), weights),
+            shifts_scores: TeacherShiftsScores::new(
+                &timetable.table1,
+                &timetable.table2,
+                timetable.max_periods_per_day,
+            ),
+            cost: CostBreakdown::default(),
+        };
+        solution.update_cost(weights);
+
+        solution
+    }
+
+    fn update_cost(&mut self, weights: &Weights) {
+        self.cost = CostBreakdown {
+            teacher_shifts: weights.teacher_shifts() * self.shifts_scores.total,
+            hard_1: self.scores1.hard,
+            soft_1: self.scores1.soft,
+            hard_2: self.scores2.hard,
+            soft_2: self.scores2.soft,
+        };
+    }
+
+    /// Applies a random neighbor of a single shift in place, scored with only the parts its move touched
+    ///
+    /// The candidate has to be passed to `settle` before the next one is proposed for the same shift.
+    pub fn propose(
+        &mut self,
+        shift: Shift,
+        weights: &Weights,
+        static_classes: &str,
+        rng: &mut impl Rng,
+    ) -> Candidate {
+        let mv = self.timetable.generate_neighbor(shift, static_classes, rng);
+
+        let scores = match shift {
+            Shift::First => &self.scores1,
+            Shift::Second => &self.scores2,
+        };
+        let update = scores.rescore(
+            &self.timetable.constraints,
+            &ShiftView::new(&self.timetable, shift),
+            weights,
+            &mv,
+        );
+
+        Candidate { mv, update }
+    }
+
+    /// Teacher shifts cost with every proposed candidate applied
+    pub fn proposed_teacher_shifts(&self, candidates: &[&Candidate], weights: &Weights) -> i32 {
+        let moves: Vec<&Move> = candidates.iter().map(|c| &c.mv).collect();
+
+        weights.teacher_shifts()
+            * self
+                .shifts_scores
+                .rescore(
+                    &self.timetable.table1,
+                    &self.timetable.table2,
+                    self.timetable.max_periods_per_day,
+                    &moves,
+                )
+                .total
+    }
+
+    /// Goes back to the best solution and applies `kicks` random moves to it, rescoring everything
+    pub fn restart_from(
+        &mut self,
+        best: &BestSolution,
+        kicks: u32,
+        weights: &Weights,
+        static_classes: &str,
+        rng: &mut impl Rng,
+    ) {
+        self.timetable.table1 = best.table1.clone();
+        self.timetable.table2 = best.table2.clone();
+
+        for _ in 0..kicks {
+            let shift = if rng.gen_bool(0.5) {
+                Shift::First
+            } else {
+                Shift::Second
+            };
+            self.timetable.generate_neighbor(shift, static_classes, rng);
+        }
+
+        *self = ScoredSolution::new(&self.timetable, weights);
+    }
+
+    /// Undoes a candidate without settling it, so other neighbors of the same solution can be proposed
+    ///
+    /// Its scores stay valid as long as nothing is settled in the meantime, so it can still be kept.
+    pub fn withdraw(&mut self, candidate: &Candidate) {
+        self.timetable.undo(&candidate.mv);
+    }
+
+    /// Applies a withdrawn candidate again and keeps it
+    pub fn keep_withdrawn(&mut self, candidate: Candidate, weights: &Weights) {
+        self.timetable.apply(&candidate.mv);
+        self.settle(vec![(candidate, true)], weights);
+    }
+
+    /// Keeps the accepted candidates and undoes the rejected ones
+    pub fn settle(&mut self, candidates: Vec<(Candidate, bool)>, weights: &Weights) {
+        let mut moves: Vec<Move> = vec![];
+
+        for (candidate, accepted) in candidates {
+            if !accepted {
+                self.timetable.undo(&candidate.mv);
+                continue;
+            }
+
+            match candidate.mv.shift {
+                Shift::First => self.scores1.apply(candidate.update),
+                Shift::Second => self.scores2.apply(candidate.update),
+            }
+            moves.push(candidate.mv);
+        }
+
+        if moves.is_empty() {
+            return;
+        }
+
+        self.shifts_scores = self.shifts_scores.rescore(
+            &self.timetable.table1,
+            &self.timetable.table2,
+            self.timetable.max_periods_per_day,
+            &moves.iter().collect::<Vec<&Move>>(),
+        );
+
+        self.update_cost(weights);
+    }
+}
+
+/// Lowest-cost tables the annealer has seen so far
+#[derive(Clone)]
+pub struct BestSolution {
+    pub table1: Vec<ClassSlots>,
+    pub table2: Vec<ClassSlots>,
+    pub cost: CostBreakdown,
+}
+
+impl BestSolution {
+    pub fn new(solution: &ScoredSolution) -> Self {
+        Self {
+            table1: solution.timetable.table1.clone(),
+            table2: solution.timetable.table2.clone(),
+            cost: solution.cost,
+        }
+    }
+
+    /// Keeps the solution if it's cheaper than the best one, returns whether it was kept
+    pub fn offer(&mut self, solution: &ScoredSolution) -> bool {
+        if solution.cost.total() >= self.cost.total() {
+            return false;
+        }
+
+        *self = Self::new(solution);
+
+        true
+    }
+}
+
+/// How the annealer decides whether to accept the neighbors of both shifts
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Acceptance {
+    /// Each shift is accepted on its own cost plus the teacher shifts cost
+    #[default]
+    Independent,
+    /// Both shifts are accepted or rejected together on the combined cost, as described in plan.txt
+    Joint,
+}
+
+impl Acceptance {
+    pub fn parse(v: &str) -> Option<Self> {
+        match v {
+            "independent" => Some(Acceptance::Independent),
+            "joint" => Some(Acceptance::Joint),
+            _ => None,
+        }
+    }
+}
+
+/// Optimizer the solver runs
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Algorithm {
+    #[default]
+    Annealing,
+    /// Moves to the best of a batch of neighbors that doesn't undo a recent move, for polishing a good solution
+    Tabu,
+}
+
+impl Algorithm {
+    pub fn parse(v: &str) -> Option<Self> {
+        match v {
+            "annealing" => Some(Algorithm::Annealing),
+            "tabu" => Some(Algorithm::Tabu),
+            _ => None,
+        }
+    }
+}
+
+/// Parameters of the annealer, sent along with the play message
+#[derive(Clone, Debug)]
+pub struct AnnealingParams {
+    pub algorithm: Algorithm,
+    pub alpha: f64,
+    pub t0: f64,
+    pub sa_max: i64,
+    pub static_classes: String,
+    pub acceptance: Acceptance,
+    pub seed: u64, // every random choice of the run follows from it
+    pub stop: StopCriteria,
+    pub escape: EscapeParams,
+    pub parallel: ParallelParams,
+    pub tabu: TabuParams,
+    pub progress_ms: u64, // time between progress reports, 0 turns them off
+    pub snapshot_ms: Option<u64>, // least time between snapshots of the best solution, none are sent without it
+}
+
+impl AnnealingParams {
+    /// Parameters from play data
+    pub fn from_json(data: &Value) -> Self {
+        let acceptance = match data["acceptance"].as_str() {
+            Some(v) => Acceptance::parse(v).unwrap_or_else(|| {
+                println!("Unknown acceptance: {}", v);
+                Acceptance::default()
+            }),
+            None => Acceptance::default(),
+        };
+
+        let algorithm = match data["algorithm"].as_str() {
+            Some(v) => Algorithm::parse(v).unwrap_or_else(|| {
+                println!("Unknown algorithm: {}", v);
+                Algorithm::default()
+            }),
+            None => Algorithm::default(),
+        };
+
+        AnnealingParams {
+            algorithm,
+            alpha: data["alpha"].as_f64().unwrap(),
+            t0: data["t0"].as_f64().unwrap(),
+            sa_max: data["sa_max"].as_i64().unwrap(),
+            static_classes: data["static_classes"].as_str().unwrap().to_string(),
+            acceptance,
+            // a run without a seed still gets one, so it can be reproduced from the echoed value
+            seed: data["seed"].as_u64().unwrap_or_else(|| thread_rng().gen()),
+            stop: StopCriteria {
+                max_seconds: data["max_seconds"].as_f64(),
+                max_iterations: data["max_iterations"].as_u64(),
+                min_temperature: data["min_temperature"].as_f64(),
+                zero_hard: data["stop_at_zero_hard"].as_bool().unwrap_or(false),
+                stagnation_steps: data["stagnation_steps"].as_u64(),
+            },
+            escape: EscapeParams::from_json(data),
+            parallel: ParallelParams {
+                chains: data["chains"].as_u64().unwrap_or(1).max(1) as usize,
+                exchange_steps: data["exchange_steps"].as_u64().unwrap_or(10).max(1),
+                temperature_ratio: data["chain_temperature_ratio"].as_f64().unwrap_or(1.0),
+            },
+            tabu: TabuParams {
+                neighbors: data["tabu_neighbors"].as_u64().unwrap_or(50).max(1),
+                tenure: data["tabu_tenure"].as_u64().unwrap_or(20) as usize,
+            },
+            progress_ms: data["progress_ms"].as_u64().unwrap_or(1000),
+            snapshot_ms: data["snapshot_ms"].as_u64(),
+        }
+    }
+}
+
+/// Conditions that end a run on their own, without waiting for a pause
+#[derive(Clone, Debug, Default)]
+pub struct StopCriteria {
+    pub max_seconds: Option<f64>,
+    pub max_iterations: Option<u64>,
+    pub min_temperature: Option<f64>,
+    pub zero_hard: bool, // stops once neither shift breaks a hard constraint
+    pub stagnation_steps: Option<u64>, // temperature (or tabu) steps in a row without a better solution
+}
+
+impl StopCriteria {
+    /// Whether a run is sure to end without a pause, tabu search has no temperature to stop at
+    pub fn bounded(&self, algorithm: Algorithm) -> bool {
+        self.max_seconds.is_some()
+            || self.max_iterations.is_some()
+            || (algorithm == Algorithm::Annealing && self.min_temperature.is_some())
+            || self.stagnation_steps.is_some()
+    }
+}
+
+/// What the annealer does once it stops finding better solutions
+#[derive(Clone, Copy, PartialEq, Serialize, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Escape {
+    /// Keeps cooling down
+    #[default]
+    None,
+    /// Raises the temperature back to a fraction of t0
+    Reheat,
+    /// Goes back to the best solution, changes it at random a few times and reheats
+    Restart,
+}
+
+impl Escape {
+    pub fn parse(v: &str) -> Option<Self> {
+        match v {
+            "none" => Some(Escape::None),
+            "reheat" => Some(Escape::Reheat),
+            "restart" => Some(Escape::Restart),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EscapeParams {
+    pub kind: Escape,
+    pub after_steps: u64, // temperature steps in a row without a better solution before escaping
+    pub fraction: f64,    // of t0 the temperature is raised to
+    pub kicks: u32,       // random moves applied to the best solution on a restart
+}
+
+impl EscapeParams {
+    pub fn from_json(data: &Value) -> Self {
+        let kind = match data["escape"].as_str() {
+            Some(v) => Escape::parse(v).unwrap_or_else(|| {
+                println!("Unknown escape: {}", v);
+                Escape::default()
+            }),
+            None => Escape::default(),
+        };
+
+        Self {
+            kind,
+            after_steps: data["escape_after"].as_u64().unwrap_or(50),
+            fraction: data["reheat_fraction"].as_f64().unwrap_or(0.5),
+            kicks: data["restart_kicks"].as_u64().unwrap_or(10) as u32,
+        }
+    }
+}
+
+/// Independent chains annealed on their own threads, which share their best solution
+#[derive(Clone, Debug)]
+pub struct ParallelParams {
+    pub chains: usize,          // 1 anneals on the calling thread only
+    pub exchange_steps: u64,    // temperature steps between sharing the best solution
+    pub temperature_ratio: f64, // t0 of every chain is the previous one's times this
+}
+
+#[derive(Clone, Debug)]
+pub struct TabuParams {
+    pub neighbors: u64, // evaluated every step
+    pub tenure: usize,  // steps a lesson can't go back to a slot it was moved out of
+}
+
+/// Why a run of the annealer ended
+#[derive(Clone, Copy, PartialEq, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    Paused,
+    TimeLimit,
+    IterationLimit,
+    MinTemperature,
+    ZeroHard,
+    Stagnation,
+}
+
+/// Outcome of a run of the annealer, the timetable is left with the best solution
+#[derive(Clone, Copy, Serialize, Debug)]
+pub struct AnnealingResult {
+    pub reason: StopReason,
+    pub seed: u64,
+    pub iterations: u64, // of the longest chain in a parallel run
+    pub escapes: u32,
+    pub elapsed_secs: f64,
+    pub current: CostBreakdown,
+    pub best: CostBreakdown,
+}
+
+/// When a run last reported its progress and sent a snapshot of its best solution, and what happened since then
+struct ProgressTracker {
+    started: Instant,
+    last_progress: Instant,
+    last_snapshot: Option<Instant>,
+    iterations: u64,    // since the last progress report
+    accepted: u64,      // since the last progress report
+    best_changed: bool, // since the last snapshot
+}
+
+impl ProgressTracker {
+    const CHECK_EVERY: u64 = 64; // iterations between looking at the clock
+
+    fn new(started: Instant) -> Self {
+        Self {
+            started,
+            last_progress: started,
+            last_snapshot: None,
+            iterations: 0,
+            accepted: 0,
+            best_changed: true,
+        }
+    }
+
+    fn record(&mut self, accepted: bool, best_changed: bool) {
+        self.iterations += 1;
+        if accepted {
+            self.accepted += 1;
+        }
+        self.best_changed |= best_changed;
+    }
+
+    /// Reports progress and snapshots the best solution if it's been long enough since the last time
+    fn report(
+        &mut self,
+        params: &AnnealingParams,
+        temperature: f64,
+        iteration: u64,
+        current: &CostBreakdown,
+        best: &BestSolution,
+        events: &mut dyn SolverEvents,
+    ) {
+        if !iteration.is_multiple_of(Self::CHECK_EVERY) {
+            return;
+        }
+        let now = Instant::now();
+
+        if params.progress_ms > 0
+            && now.duration_since(self.last_progress).as_millis() >= params.progress_ms as u128
+        {
+            events.progress(&Progress {
+                seed: params.seed,
+                temperature,
+                iteration,
+                current: *current,
+                best: best.cost,
+                acceptance_rate: self.accepted as f64 / self.iterations as f64,
+                elapsed_secs: now.duration_since(self.started).as_secs_f64(),
+            });
+
+            self.last_progress = now;
+            self.iterations = 0;
+            self.accepted = 0;
+        }
+
+        if let Some(snapshot_ms) = params.snapshot_ms {
+            let due = self
+                .last_snapshot
+                .is_none_or(|last| now.duration_since(last).as_millis() >= snapshot_ms as u128);
+
+            if self.best_changed && due {
+                events.best_snapshot(&best.table1, &best.table2, &best.cost);
+
+                self.last_snapshot = Some(now);
+                self.best_changed = false;
+            }
+        }
+    }
+}
+
+/// Whether a solution that changes the cost by `delta` is accepted at temperature `t`
+fn accepts(delta: i32, t: f64, rng: &mut impl Rng) -> bool {
+    if delta <= 0 {
+        return true;
+    }
+
+    let x: f64 = rng.gen_range(0.0..1.0);
+
+    let base: f64 = std::f64::consts::E;
+
+    let exponent = (-delta as f64) / t;
+
+    let chance = base.powf(exponent);
+    x < chance
+}
+
+/// Best solution of all the chains of a parallel run
+struct Exchange {
+    best: Mutex<BestSolution>,
+    stopped: OnceLock<StopReason>, // set by the first chain that meets a stopping criterion, which ends every chain
+}
+
+impl Exchange {
+    fn new(best: BestSolution) -> Self {
+        Self {
+            best: Mutex::new(best),
+            stopped: OnceLock::new(),
+        }
+    }
+
+    /// Publishes the chain's best solution if it's the best of all, otherwise moves the chain to the best of all if that's better than where it is
+    fn share(
+        &self,
+        current: &mut ScoredSolution,
+        best: &mut BestSolution,
+        weights: &Weights,
+        static_classes: &str,
+        rng: &mut impl Rng,
+    ) {
+        let mut shared = self.best.lock().unwrap();
+
+        if best.cost.total() < shared.cost.total() {
+            *shared = best.clone();
+        } else if shared.cost.total() < current.cost.total() {
+            current.restart_from(&shared, 0, weights, static_classes, rng);
+            best.offer(current);
+        }
+    }
+}
+
+/// (class, relation, slot) of a lesson
+type Placement = (usize, usize, usize);
+
+/// Lessons moved out of a slot during the last `tenure` steps, which can't be moved back yet
+struct TabuList {
+    tenure: usize,
+    steps: VecDeque<Vec<Placement>>,
+}
+
+impl TabuList {
+    fn new(tenure: usize) -> Self {
+        Self {
+            tenure,
+            steps: VecDeque::new(),
+        }
+    }
+
+    fn forbids(&self, placements: &[Placement]) -> bool {
+        self.steps
+            .iter()
+            .any(|step| placements.iter().any(|p| step.contains(p)))
+    }
+
+    /// Whether a move to a solution costing `cost` can be taken, tabu moves still can when they beat the best solution
+    fn admits(&self, placements: &[Placement], cost: i32, best: i32) -> bool {
+        cost < best || !self.forbids(placements)
+    }
+
+    fn push(&mut self, placements: Vec<Placement>) {
+        self.steps.push_back(placements);
+        while self.steps.len() > self.tenure {
+            self.steps.pop_front();
+        }
+    }
+}
+
+/// Seed and starting temperature of a single annealing chain
+#[derive(Clone, Copy, Debug)]
+struct Chain {
+    seed: u64,
+    t0: f64,
+}
+
+/// How a single annealing chain ended
+struct ChainOutcome {
+    reason: StopReason,
+    iterations: u64,
+    escapes: u32,
+    current: CostBreakdown,
+    best: BestSolution,
+}
+
+/// Slots of a single class changed by a neighbor, along with what they held before and after
+#[derive(Clone, Debug)]
+pub struct Move {
+    pub shift: Shift,
+    pub class: usize,                      // index into the shift's table
+    pub changes: Vec<(usize, Slot, Slot)>, // (index, before, after)
+}
+
+impl Move {
+    pub fn between(shift: Shift, class: usize, before: &ClassSlots, after: &ClassSlots) -> Self {
+        let changes = before
+            .slots
+            .iter()
+            .zip(after.slots.iter())
+            .enumerate()
+            .filter(|(_, (b, a))| b != a)
+            .map(|(i, (b, a))| (i, *b, *a))
+            .collect();
+
+        Self {
+            shift,
+            class,
+            changes,
+        }
+    }
+
+    /// Days of every changed slot, without duplicates
+    pub fn days(&self, max_periods_per_day: u32) -> Vec<u32> {
+        let mut days: Vec<u32> = vec![];
+        for (index, _, _) in self.changes.iter() {
+            let day = *index as u32 / max_periods_per_day;
+            if !days.contains(&day) {
+                days.push(day);
+            }
+        }
+
+        days
+    }
+
+    /// Teachers of every lesson in a changed slot, both before and after the move, without duplicates
+    pub fn teachers(&self) -> Vec<usize> {
+        let mut teachers: Vec<usize> = vec![];
+        for (_, before, after) in self.changes.iter() {
+            for slot in [before, after] {
+                let slot_datas = match *slot {
+                    Slot::Single(s) => [s, SlotData::Empty],
+                    Slot::Double { first, second, .. } => [first, second],
+                };
+
+                for slot_data in slot_datas {
+                    if let SlotData::PartiallyFilled { teacher, .. } = slot_data {
+                        if !teachers.contains(&teacher) {
+                            teachers.push(teacher);
+                        }
+                    }
+                }
+            }
+        }
+
+        teachers
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
 pub enum Shift {
     First,
     Second,
@@ -79,7 +815,7 @@ impl Shift {
 
 #[derive(Clone, Serialize, Debug)]
 pub struct Timetable {
-    pub data: TimetableData,
+    pub data: Arc<TimetableData>, // shared between every solution the solver works on
 
     pub max_periods_per_day: u32,
 
@@ -87,6 +823,11 @@ pub struct Timetable {
     // Inner vector: Single class' timeslots - mon(1,2,3,4,5,6,7), tue(1,2,3,4,5,6,7), wed(1,2,3,4,5,6,7), thu(1,2,3,4,5,6,7), fri(1,2,3,4,5,6,7)
     pub table1: Vec<ClassSlots>,
     pub table2: Vec<ClassSlots>,
+
+    #[serde(skip)]
+    pub constraints: Arc<ConstraintRegistry>,
+
+    pub seed: Option<u64>, // seed of the run that produced the tables, if they came from one
 }
 
 impl Timetable {
@@ -97,13 +838,23 @@ impl Timetable {
         table2: Vec<ClassSlots>,
     ) -> Self {
         Self {
-            data,
+            data: Arc::new(data),
             max_periods_per_day,
             table1,
             table2,
+            constraints: Arc::new(ConstraintRegistry::default()),
+            seed: None,
         }
     }
 
+    /// Copies the data first if any solution still shares it, the lookups built from it are dropped
+    pub fn data_mut(&mut self) -> &mut TimetableData {
+        let data = Arc::make_mut(&mut self.data);
+        data.lookups.take();
+
+        data
+    }
+
     pub fn table(&self, shift: Shift) -> &Vec<ClassSlots> {
         match shift {
             Shift::First => &self.table1,
@@ -111,6 +862,22 @@ impl Timetable {
         }
     }
 
+    /// Sets every slot changed by the move to what it held after it
+    pub fn apply(&mut self, mv: &Move) {
+        let slots = &mut self.table_mut(mv.shift)[mv.class].slots;
+        for (index, _, after) in mv.changes.iter() {
+            slots[*index] = *after;
+        }
+    }
+
+    /// Sets every slot changed by the move back to what it held before it
+    pub fn undo(&mut self, mv: &Move) {
+        let slots = &mut self.table_mut(mv.shift)[mv.class].slots;
+        for (index, before, _) in mv.changes.iter() {
+            slots[*index] = *before;
+        }
+    }
+
     pub fn table_mut(&mut self, shift: Shift) -> &mut Vec<ClassSlots> {
         match shift {
             Shift::First => &mut self.table1,
@@ -118,7 +885,7 @@ impl Timetable {
         }
     }
 
-    pub fn generate_random_table(&mut self, shift: Shift, _out: &Sender) {
+    pub fn generate_random_table(&mut self, shift: Shift) {
         let mut table: Vec<ClassSlots> = vec![];
         table.resize(
             self.data.classes.len(),
@@ -522,273 +1289,446 @@ impl Timetable {
         }
     }
 
+    /// Anneals both tables until `running` is cleared or a stopping criterion is met, then keeps the best solution
     pub fn start_algorithm(
         &mut self,
-        running: Arc<AtomicBool>,
-        out: &Sender,
-        alpha: f64,
-        t0: f64,
-        sa_max: i64,
-        static_classes: &String,
-        hard_1: i32,
-        soft_1: i32,
-        hard_2: i32,
-        soft_2: i32,
-    ) {
-        // SIMULATED ANNEALLING:
-        {
-            let mut s1 = self.clone();
-            let mut s2 = self.clone();
-            // let mut best_s1 = self.clone();
-            // let mut best_s2 = self.clone();
-
-            let mut t = t0;
-
-            while running.load(Ordering::Relaxed) {
-                for _ in 0..sa_max {
-                    let new_s1 = s1.generate_neighbor(Shift::First, out, static_classes);
-                    let new_s2 = s2.generate_neighbor(Shift::Second, out, static_classes);
-
-                    let new_s_cost_shifts = cost::teacher_shifts(
-                        &new_s1.table1,
-                        &new_s2.table2,
-                        self.max_periods_per_day,
-                        &self.data,
-                        false,
-                    );
-                    let s_cost_shifts = cost::teacher_shifts(
-                        &s1.table1,
-                        &s2.table2,
-                        self.max_periods_per_day,
-                        &self.data,
-                        false,
-                    );
-
-                    let new_s1_cost_hard = new_s1.hard_points(Shift::First, hard_1);
-                    let s1_cost_hard = s1.hard_points(Shift::First, hard_1);
+        running: &AtomicBool,
+        params: &AnnealingParams,
+        weights: &Weights,
+        events: &mut dyn SolverEvents,
+    ) -> AnnealingResult {
+        events.started(params);
+
+        let started = Instant::now();
+
+        let outcome = if params.algorithm == Algorithm::Tabu {
+            self.tabu_search(running, params, weights, events)
+        } else if params.parallel.chains > 1 {
+            self.parallel_chains(running, params, weights, events)
+        } else {
+            let chain = Chain {
+                seed: params.seed,
+                t0: params.t0,
+            };
+            self.anneal_chain(running, params, chain, weights, events, None)
+        };
 
-                    let new_s1_cost_soft = new_s1.soft_points(Shift::First, soft_1);
-                    let s1_cost_soft = s1.soft_points(Shift::First, soft_1);
+        // the last accepted solution can be much worse than one seen earlier at a higher temperature
+        self.table1 = outcome.best.table1;
+        self.table2 = outcome.best.table2;
+        self.seed = Some(params.seed);
+
+        let result = AnnealingResult {
+            reason: outcome.reason,
+            seed: params.seed,
+            iterations: outcome.iterations,
+            escapes: outcome.escapes,
+            elapsed_secs: started.elapsed().as_secs_f64(),
+            current: outcome.current,
+            best: outcome.best.cost,
+        };
+        events.finished(&result, self, &self.detailed_cost(weights));
 
-                    let new_s1_cost = new_s1_cost_hard + new_s1_cost_soft + new_s_cost_shifts;
-                    let s1_cost = s1_cost_hard + s1_cost_soft + s_cost_shifts;
+        result
+    }
 
-                    let delta1 = new_s1_cost - s1_cost;
+    /// Runs a chain per thread, the first one on this thread reporting to `events`, and keeps the best outcome
+    ///
+    /// Chains differ by seed and starting temperature, and move to the best solution of all of them every
+    /// `exchange_steps` temperature steps when it's better than where they are.
+    fn parallel_chains(
+        &self,
+        running: &AtomicBool,
+        params: &AnnealingParams,
+        weights: &Weights,
+        events: &mut dyn SolverEvents,
+    ) -> ChainOutcome {
+        let exchange = Exchange::new(BestSolution::new(&ScoredSolution::new(self, weights)));
+
+        let chain = |i: usize| Chain {
+            seed: params.seed.wrapping_add(i as u64),
+            t0: params.t0 * params.parallel.temperature_ratio.powi(i as i32),
+        };
 
-                    let mut updated1 = false;
-                    if delta1 <= 0 {
-                        s1 = new_s1;
-                        updated1 = true;
-                    } else {
-                        let x: f64 = thread_rng().gen_range(0.0..1.0);
+        let outcomes: Vec<ChainOutcome> = thread::scope(|scope| {
+            let workers: Vec<_> = (1..params.parallel.chains)
+                .map(|i| {
+                    let exchange = &exchange;
+                    scope.spawn(move || {
+                        self.anneal_chain(
+                            running,
+                            params,
+                            chain(i),
+                            weights,
+                            &mut NoEvents,
+                            Some(exchange),
+                        )
+                    })
+                })
+                .collect();
 
-                        let base: f64 = std::f64::consts::E;
+            let mut outcomes = vec![self.anneal_chain(
+                running,
+                params,
+                chain(0),
+                weights,
+                events,
+                Some(&exchange),
+            )];
+            for worker in workers {
+                outcomes.push(worker.join().unwrap());
+            }
 
-                        let exponent = (-delta1 as f64) / (t as f64);
+            outcomes
+        });
+
+        // every chain runs the whole budget, so the longest one is what counts against `max_iterations`
+        let iterations = outcomes.iter().map(|o| o.iterations).max().unwrap();
+        let escapes = outcomes.iter().map(|o| o.escapes).sum();
+        let reason = exchange
+            .stopped
+            .get()
+            .copied()
+            .unwrap_or(StopReason::Paused);
+
+        let best = outcomes
+            .into_iter()
+            .min_by_key(|o| o.best.cost.total())
+            .unwrap();
+
+        ChainOutcome {
+            reason,
+            iterations,
+            escapes,
+            ..best
+        }
+    }
 
-                        let chance = base.powf(exponent);
-                        if x < chance {
-                            s1 = new_s1;
-                            updated1 = true;
-                        }
-                    }
-                    // ================
-                    //let new_s2 = s2.generate_neighbor(Shift::Second, out, static_classes);
+    /// A single annealing chain starting from this timetable, which is left as is
+    fn anneal_chain(
+        &self,
+        running: &AtomicBool,
+        params: &AnnealingParams,
+        chain: Chain,
+        weights: &Weights,
+        events: &mut dyn SolverEvents,
+        exchange: Option<&Exchange>,
+    ) -> ChainOutcome {
+        let static_classes = &params.static_classes;
+        let mut rng = StdRng::seed_from_u64(chain.seed);
 
-                    let new_s2_cost_hard = new_s2.hard_points(Shift::Second, hard_2);
-                    let s2_cost_hard = s2.hard_points(Shift::Second, hard_2);
+        // SIMULATED ANNEALLING:
+        {
+            let mut current = ScoredSolution::new(self, weights);
+            let mut best = BestSolution::new(&current);
+
+            let stop = &params.stop;
+            let started = Instant::now();
+
+            let mut t = chain.t0;
+            let mut iterations: u64 = 0;
+            let mut stagnant_steps: u64 = 0;
+            let mut steps_since_escape: u64 = 0; // without a better solution
+            let mut escapes: u32 = 0;
+            let mut steps: u64 = 0;
+            let mut tracker = ProgressTracker::new(started);
+
+            let reason = 'annealing: loop {
+                if !running.load(Ordering::Relaxed) {
+                    break StopReason::Paused;
+                }
 
-                    let new_s2_cost_soft = new_s2.soft_points(Shift::Second, soft_2);
-                    let s2_cost_soft = s2.soft_points(Shift::Second, soft_2);
+                let mut improved_this_step = false;
 
-                    let new_s2_cost = new_s2_cost_hard + new_s2_cost_soft + new_s_cost_shifts;
-                    let s2_cost = s2_cost_hard + s2_cost_soft + s_cost_shifts;
+                for _ in 0..params.sa_max {
+                    if let Some(reason) = exchange.and_then(|e| e.stopped.get()) {
+                        break 'annealing *reason;
+                    }
+                    if stop.max_iterations.is_some_and(|max| iterations >= max) {
+                        break 'annealing StopReason::IterationLimit;
+                    }
+                    if stop
+                        .max_seconds
+                        .is_some_and(|max| started.elapsed().as_secs_f64() >= max)
+                    {
+                        break 'annealing StopReason::TimeLimit;
+                    }
+                    iterations += 1;
 
-                    let delta2 = new_s2_cost - s2_cost;
+                    let candidate1 =
+                        current.propose(Shift::First, weights, static_classes, &mut rng);
+                    let candidate2 =
+                        current.propose(Shift::Second, weights, static_classes, &mut rng);
 
-                    let mut updated2 = false;
-                    if delta2 <= 0 {
-                        s2 = new_s2;
-                        updated2 = true;
-                    } else {
-                        let x: f64 = thread_rng().gen_range(0.0..1.0);
+                    // both candidates are scored against each other for the teacher shifts cost
+                    let new_s_cost_shifts =
+                        current.proposed_teacher_shifts(&[&candidate1, &candidate2], weights);
 
-                        let base: f64 = std::f64::consts::E;
+                    let new_s1_cost = candidate1.update.hard + candidate1.update.soft;
+                    let new_s2_cost = candidate2.update.hard + candidate2.update.soft;
 
-                        let exponent = (-delta2 as f64) / (t as f64);
+                    let (updated1, updated2) = match params.acceptance {
+                        Acceptance::Independent => {
+                            let delta1 =
+                                new_s1_cost + new_s_cost_shifts - current.cost.shift(Shift::First);
+                            let delta2 =
+                                new_s2_cost + new_s_cost_shifts - current.cost.shift(Shift::Second);
 
-                        let chance = base.powf(exponent);
-                        if x < chance {
-                            s2 = new_s2;
-                            updated2 = true;
+                            (accepts(delta1, t, &mut rng), accepts(delta2, t, &mut rng))
                         }
-                    }
-                    // ================
+                        Acceptance::Joint => {
+                            // all 5 costs together, both shifts change or neither does
+                            let delta = new_s1_cost + new_s2_cost + new_s_cost_shifts
+                                - current.cost.total();
 
+                            let updated = accepts(delta, t, &mut rng);
+                            (updated, updated)
+                        }
+                    };
+
+                    current.settle(
+                        vec![(candidate1, updated1), (candidate2, updated2)],
+                        weights,
+                    );
+
+                    let mut improved = false;
                     if updated1 || updated2 {
-                        let (hard1, soft1) = if updated1 {
-                            (new_s1_cost_hard, new_s1_cost_soft)
-                        } else {
-                            (s1_cost_hard, s1_cost_soft)
-                        };
-                        let (hard2, soft2) = if updated2 {
-                            (new_s2_cost_hard, new_s2_cost_soft)
-                        } else {
-                            (s2_cost_hard, s2_cost_soft)
-                        };
+                        improved = best.offer(&current);
 
-                        println!(
-                            "[TEMP: {}] [{: >3}]   [{: >3}, {: >3}]    [{: >3}, {: >3}]",
-                            //"[TEMP: {}]    [{: >3}, {: >3}]",
-                            t,
-                            new_s_cost_shifts,
-                            hard1,
-                            soft1,
-                            hard2,
-                            soft2,
+                        events.accepted(t, &current.cost, &best.cost);
+                    }
+
+                    tracker.record(updated1 || updated2, improved);
+                    tracker.report(params, t, iterations, &current.cost, &best, events);
+                    improved_this_step |= improved;
+
+                    if stop.zero_hard && current.cost.hard_1 + current.cost.hard_2 == 0 {
+                        break 'annealing StopReason::ZeroHard;
+                    }
+                }
+
+                t *= params.alpha;
+                steps += 1;
+
+                if let Some(exchange) = exchange {
+                    if steps.is_multiple_of(params.parallel.exchange_steps) {
+                        exchange.share(&mut current, &mut best, weights, static_classes, &mut rng);
+                    }
+                }
+
+                steps_since_escape = if improved_this_step {
+                    0
+                } else {
+                    steps_since_escape + 1
+                };
+                if params.escape.kind != Escape::None
+                    && steps_since_escape >= params.escape.after_steps
+                {
+                    if params.escape.kind == Escape::Restart {
+                        current.restart_from(
+                            &best,
+                            params.escape.kicks,
+                            weights,
+                            static_classes,
+                            &mut rng,
                         );
                     }
+                    t = chain.t0 * params.escape.fraction;
+
+                    steps_since_escape = 0;
+                    escapes += 1;
+                    events.escaped(params.escape.kind, t, &current.cost);
+                }
+
+                if stop.min_temperature.is_some_and(|min| t < min) {
+                    break StopReason::MinTemperature;
                 }
 
-                t = t * alpha;
+                stagnant_steps = if improved_this_step {
+                    0
+                } else {
+                    stagnant_steps + 1
+                };
+                if stop
+                    .stagnation_steps
+                    .is_some_and(|max| stagnant_steps >= max)
+                {
+                    break StopReason::Stagnation;
+                }
+            };
+
+            if let Some(exchange) = exchange {
+                if reason != StopReason::Paused {
+                    let _ = exchange.stopped.set(reason);
+                }
             }
 
-            self.table1 = s1.table1;
-            self.table2 = s2.table2;
+            ChainOutcome {
+                reason,
+                iterations,
+                escapes,
+                current: current.cost,
+                best,
+            }
         }
+    }
 
-        self.detailed_cost(hard_1, soft_1, hard_2, soft_2);
-    }
-
-    pub fn detailed_cost(&self, hard_1: i32, soft_1: i32, hard_2: i32, soft_2: i32) {
-        let teacher_table1 =
-            util::class_table_to_teacher_table(&self.table1, &self.data, self.max_periods_per_day);
-        let teacher_table2 =
-            util::class_table_to_teacher_table(&self.table2, &self.data, self.max_periods_per_day);
-
-        println!("DETAILED COST");
-        /*
-        let cost_shifts = cost::teacher_shifts(&self.table1, &self.table2, self.max_periods_per_day);
-        println!("Shifts: {}", cost_shifts);
-        println!("--------------");
-        */
-
-        let hcost1 = hard_1 * cost::hard_repeating_teachers(self, Shift::First, false);
-        let hcost2 = hard_1 * cost::hard_holes_in_class_timetable(self, Shift::First);
-        let hcost3 = hard_1 * cost::hard_too_many_subjects_of_same_kind(self, Shift::First, false);
-        let hcost4 = hard_1 * cost::hard_block_classes(self, Shift::First);
-        let hcost5 = hard_1 * cost::hard_specific_subject_days(self, Shift::First);
-        let hcost6 = hard_1 * cost::hard_subject_per_day_limits(self, Shift::First);
-        let hcost7 = hard_1 * cost::hard_subject_holes(self, Shift::First, false);
-        let hcost8 = hard_1 * cost::hard_teacher_shift_spread(self, Shift::First, false);
-        let hcost9 = hard_1 * cost::hard_teacher_extra_constraints(self, Shift::First);
-
-        let scost1 = soft_1 * cost::soft_class_spread(self, Shift::First);
-        let scost2 = soft_1 * cost::soft_teacher_class_spread(self, &teacher_table1);
-        let scost3 = soft_1 * cost::soft_holes_in_teacher_timetable(self, &teacher_table1);
-        let scost4 = soft_1 * cost::soft_preferred_subject_times(self, Shift::First);
-
-        println!("---");
-        println!("Repeating teachers:");
-        cost::hard_repeating_teachers(self, Shift::First, true);
-        println!("Teacher shift spread:");
-        cost::hard_teacher_shift_spread(self, Shift::First, true);
-        println!("Subject holes:");
-        cost::hard_subject_holes(self, Shift::First, true);
-        println!("Repeating rooms:");
-        cost::repeating_rooms(self, Shift::First, true);
-        println!("Too many subjects of same kind:");
-        cost::hard_too_many_subjects_of_same_kind(self, Shift::First, true);
-
-        println!(
-            "FIRST SHIFT ({}, {}):",
-            hcost1 + hcost2 + hcost3 + hcost4 + hcost5 + hcost6 + hcost7 + hcost8 + hcost9,
-            scost1 + scost2 + scost3 + scost4
-        );
-        println!("  (h) Repeating teachers: {}", hcost1);
-        println!("  (h) Holes: {}", hcost2);
-        println!("  (h) Too many subjects of same kind: {}", hcost3);
-        println!("  (h) Block classes: {}", hcost4);
-        println!("  (h) Specific subject days: {}", hcost5);
-        println!("  (h) Subject per day limits: {}", hcost6);
-        println!("  (h) Subject holes: {}", hcost7);
-        println!("  (h) Teacher shift spread: {}", hcost8);
-        println!("  (h) Teacher extra constraints: {}", hcost9);
-        println!("  (s) Class spread: {}", scost1);
-        println!("  (s) Teacher class spread: {}", scost2);
-        println!("  (s) Teacher holes: {}", scost3);
-        println!("  (s) Soft preferred subject times: {}", scost4);
-
-        let hcost1 = hard_2 * cost::hard_repeating_teachers(self, Shift::Second, false);
-        let hcost2 = hard_2 * cost::hard_holes_in_class_timetable(self, Shift::Second);
-        let hcost3 = hard_2 * cost::hard_too_many_subjects_of_same_kind(self, Shift::Second, false);
-        let hcost4 = hard_2 * cost::hard_block_classes(self, Shift::Second);
-        let hcost5 = hard_2 * cost::hard_specific_subject_days(self, Shift::Second);
-        let hcost6 = hard_2 * cost::hard_subject_per_day_limits(self, Shift::Second);
-        let hcost7 = hard_2 * cost::hard_subject_holes(self, Shift::Second, false);
-        let hcost8 = hard_2 * cost::hard_teacher_shift_spread(self, Shift::Second, false);
-        let hcost9 = hard_2 * cost::hard_teacher_extra_constraints(self, Shift::Second);
-
-        let scost1 = soft_2 * cost::soft_class_spread(self, Shift::Second);
-        let scost2 = soft_2 * cost::soft_teacher_class_spread(self, &teacher_table2);
-        let scost3 = soft_2 * cost::soft_holes_in_teacher_timetable(self, &teacher_table2);
-        let scost4 = soft_2 * cost::soft_preferred_subject_times(self, Shift::Second);
-
-        println!("---");
-        println!("Repeating teachers:");
-        cost::hard_repeating_teachers(self, Shift::Second, true);
-        println!("Teacher shift spread:");
-        cost::hard_teacher_shift_spread(self, Shift::Second, true);
-        println!("Subject holes:");
-        cost::hard_subject_holes(self, Shift::Second, true);
-        println!("Repeating rooms:");
-        cost::repeating_rooms(self, Shift::Second, true);
-        println!("Too many subjects of same kind:");
-        cost::hard_too_many_subjects_of_same_kind(self, Shift::Second, true);
-
-        println!(
-            "SECOND SHIFT ({}, {}):",
-            hcost1 + hcost2 + hcost3 + hcost4 + hcost5 + hcost6 + hcost7 + hcost8 + hcost9,
-            scost1 + scost2 + scost3 + scost4
-        );
-        println!("  (h) Repeating teachers: {}", hcost1);
-        println!("  (h) Holes: {}", hcost2);
-        println!("  (h) Too many subjects of same kind: {}", hcost3);
-        println!("  (h) Block classes: {}", hcost4);
-        println!("  (h) Specific subject days: {}", hcost5);
-        println!("  (h) Subject per day limits: {}", hcost6);
-        println!("  (h) Subject holes: {}", hcost7);
-        println!("  (h) Teacher shift spread: {}", hcost8);
-        println!("  (h) Teacher extra constraints: {}", hcost9);
-        println!("  (s) Class spread: {}", scost1);
-        println!("  (s) Teacher class spread: {}", scost2);
-        println!("  (s) Teacher holes: {}", scost3);
-        println!("  (s) Soft preferred subject times: {}", scost4);
-
-        println!("========================");
-        println!("Teacher shifts:");
-        cost::teacher_shifts(
-            &self.table1,
-            &self.table2,
-            self.max_periods_per_day,
-            &self.data,
-            true,
+    /// Tabu search starting from this timetable, which is left as is
+    ///
+    /// Every step moves to the best of a batch of random neighbors, even when it's worse, unless the move
+    /// puts a lesson back into a slot it recently left. Such a move is still taken when it beats the best solution.
+    fn tabu_search(
+        &self,
+        running: &AtomicBool,
+        params: &AnnealingParams,
+        weights: &Weights,
+        events: &mut dyn SolverEvents,
+    ) -> ChainOutcome {
+        let static_classes = &params.static_classes;
+        let mut rng = StdRng::seed_from_u64(params.seed);
+
+        let mut current = ScoredSolution::new(self, weights);
+        let mut best = BestSolution::new(&current);
+
+        let stop = &params.stop;
+        let started = Instant::now();
+
+        let mut iterations: u64 = 0;
+        let mut stagnant_steps: u64 = 0;
+        let mut tabu = TabuList::new(params.tabu.tenure);
+        let mut tracker = ProgressTracker::new(started);
+
+        let reason = loop {
+            if !running.load(Ordering::Relaxed) {
+                break StopReason::Paused;
+            }
+            if stop.max_iterations.is_some_and(|max| iterations >= max) {
+                break StopReason::IterationLimit;
+            }
+            if stop
+                .max_seconds
+                .is_some_and(|max| started.elapsed().as_secs_f64() >= max)
+            {
+                break StopReason::TimeLimit;
+            }
+            iterations += 1;
+
+            // best admissible neighbor and the total cost with it
+            let mut chosen: Option<(Candidate, i32)> = None;
+
+            for _ in 0..params.tabu.neighbors {
+                let shift = if rng.gen_bool(0.5) {
+                    Shift::First
+                } else {
+                    Shift::Second
+                };
+
+                let candidate = current.propose(shift, weights, static_classes, &mut rng);
+                let cost = current.cost.total() - current.cost.shift(shift)
+                    + candidate.update.hard
+                    + candidate.update.soft
+                    + current.proposed_teacher_shifts(&[&candidate], weights);
+
+                let admissible = tabu.admits(
+                    &current.timetable.placements(&candidate.mv, true),
+                    cost,
+                    best.cost.total(),
+                );
+
+                current.withdraw(&candidate);
+
+                if admissible && chosen.as_ref().is_none_or(|(_, c)| cost < *c) {
+                    chosen = Some((candidate, cost));
+                }
+            }
+
+            // every neighbor of the batch can be tabu
+            let moved = chosen.is_some();
+            let mut improved = false;
+            if let Some((candidate, _)) = chosen {
+                tabu.push(current.timetable.placements(&candidate.mv, false));
+                current.keep_withdrawn(candidate, weights);
+
+                improved = best.offer(&current);
+
+                events.accepted(0.0, &current.cost, &best.cost);
+            }
+
+            tracker.record(moved, improved);
+            tracker.report(params, 0.0, iterations, &current.cost, &best, events);
+
+            if stop.zero_hard && current.cost.hard_1 + current.cost.hard_2 == 0 {
+                break StopReason::ZeroHard;
+            }
+
+            stagnant_steps = if improved { 0 } else { stagnant_steps + 1 };
+            if stop
+                .stagnation_steps
+                .is_some_and(|max| stagnant_steps >= max)
+            {
+                break StopReason::Stagnation;
+            }
+        };
+
+        ChainOutcome {
+            reason,
+            iterations,
+            escapes: 0,
+            current: current.cost,
+            best,
+        }
+    }
+
+    pub fn detailed_cost(&self, weights: &Weights) -> CostReport {
+        // spans both shifts, so it isn't part of the per-shift constraints
+        let mut teacher_shifts = constraint_report(
+            "teacher_shifts",
+            "Teacher shifts",
+            false,
+            None,
+            weights.teacher_shifts(),
+            |violations| {
+                cost::teacher_shifts(
+                    &self.table1,
+                    &self.table2,
+                    self.max_periods_per_day,
+                    violations,
+                )
+            },
         );
-        println!("========================");
+        teacher_shifts.explanation =
+            String::from("Teachers shouldn't teach in both shifts during the same day");
+
+        CostReport {
+            shifts: vec![
+                self.shift_report(Shift::First, weights),
+                self.shift_report(Shift::Second, weights),
+            ],
+            teacher_shifts,
+        }
     }
 
-    pub fn generate_neighbor(&self, shift: Shift, _out: &Sender, static_classes: &String) -> Self {
-        let mut rng = thread_rng();
+    fn shift_report(&self, shift: Shift, weights: &Weights) -> ShiftReport {
+        let view = ShiftView::new(self, shift);
 
-        let mut timetable = self.clone();
+        ShiftReport {
+            shift: shift.to_i32(),
+            hard: self.constraints.points(&view, true, weights),
+            soft: self.constraints.points(&view, false, weights),
+            constraints: self.constraints.reports(&view, weights),
+        }
+    }
 
+    /// Changes a single class of the shift in place and returns the move that undoes it
+    pub fn generate_neighbor(
+        &mut self,
+        shift: Shift,
+        static_classes: &str,
+        rng: &mut impl Rng,
+    ) -> Move {
         let class_index = {
             let mut c: usize = rng.gen_range(0..self.data.classes.len());
-            let sc: Vec<String> = static_classes
-                .as_str()
-                .split(',')
-                .map(|s| s.to_string())
-                .collect();
+            let sc: Vec<String> = static_classes.split(',').map(|s| s.to_string()).collect();
 
             while sc.contains(&self.data.classes[c].name) {
                 c = rng.gen_range(0..self.data.classes.len());
@@ -797,31 +1737,43 @@ impl Timetable {
             c
         };
 
-        let mut start_index = rng.gen_range(0..timetable.table(shift)[class_index].slots.len());
+        let before = self.table(shift)[class_index].clone();
+
+        let mut start_index = rng.gen_range(0..self.table(shift)[class_index].slots.len());
 
         // println!("Class: {}", self.data.classes[class_index].name);
-        match timetable.table(shift)[class_index].slots[start_index] {
+        match self.table(shift)[class_index].slots[start_index] {
             Slot::Single(_) => {
-                let mut end_index =
-                    rng.gen_range(0..timetable.table(shift)[class_index].slots.len());
-
-                // println!("{} - {}", start_index, end_index);
-
-                // regenerate if the slot at `end_index` is a Double block
-                loop {
-                    match timetable.table(shift)[class_index].slots[end_index] {
-                        Slot::Single(_) => break,
-                        Slot::Double { .. } => {
-                            end_index =
-                                rng.gen_range(0..timetable.table(shift)[class_index].slots.len())
+                // lessons of relations that are held in blocks get moved along with the rest of their block
+                let moved = match self.block_run(shift, class_index, start_index) {
+                    Some((run_start, length)) => {
+                        self.move_run(shift, class_index, run_start, length, rng)
+                    }
+                    None => false,
+                };
+
+                if !moved {
+                    let mut end_index =
+                        rng.gen_range(0..self.table(shift)[class_index].slots.len());
+
+                    // println!("{} - {}", start_index, end_index);
+
+                    // regenerate if the slot at `end_index` is a Double block
+                    loop {
+                        match self.table(shift)[class_index].slots[end_index] {
+                            Slot::Single(_) => break,
+                            Slot::Double { .. } => {
+                                end_index =
+                                    rng.gen_range(0..self.table(shift)[class_index].slots.len())
+                            }
                         }
                     }
-                }
 
-                let tmp = timetable.table(shift)[class_index].slots[start_index];
-                timetable.table_mut(shift)[class_index].slots[start_index] =
-                    timetable.table(shift)[class_index].slots[end_index];
-                timetable.table_mut(shift)[class_index].slots[end_index] = tmp;
+                    let tmp = self.table(shift)[class_index].slots[start_index];
+                    self.table_mut(shift)[class_index].slots[start_index] =
+                        self.table(shift)[class_index].slots[end_index];
+                    self.table_mut(shift)[class_index].slots[end_index] = tmp;
+                }
             }
             Slot::Double {
                 first: _,
@@ -840,16 +1792,16 @@ impl Timetable {
                     // choose a random day
                     let day = rng.gen_range(0..5);
 
-                    let index = day * timetable.max_periods_per_day
-                        + rng.gen_range(0..(timetable.max_periods_per_day - length));
+                    let index = day * self.max_periods_per_day
+                        + rng.gen_range(0..(self.max_periods_per_day - length));
 
                     // println!("{} - {}", start_index, index);
 
-                    match timetable.table(shift)[class_index].slots[index as usize] {
+                    match self.table(shift)[class_index].slots[index as usize] {
                         Slot::Single(_) => {
                             let mut singles_in_a_row = 0;
                             for j in 0..length {
-                                match timetable.table(shift)[class_index].slots
+                                match self.table(shift)[class_index].slots
                                     [index as usize + j as usize]
                                 {
                                     Slot::Single(_) => singles_in_a_row += 1,
@@ -858,19 +1810,9 @@ impl Timetable {
                             }
 
                             if singles_in_a_row == length {
-                                for j in 0..length {
-                                    let a = timetable.table(shift)[class_index].slots
-                                        [start_index + j as usize]
-                                        .clone();
-                                    let b = timetable.table(shift)[class_index].slots
-                                        [index as usize + j as usize]
-                                        .clone();
-
-                                    timetable.table_mut(shift)[class_index].slots
-                                        [start_index + j as usize] = b;
-
-                                    timetable.table_mut(shift)[class_index].slots
-                                        [index as usize + j as usize] = a;
+                                let slots = &mut self.table_mut(shift)[class_index].slots;
+                                for j in 0..length as usize {
+                                    slots.swap(start_index + j, index as usize + j);
                                 }
 
                                 done = true;
@@ -884,19 +1826,10 @@ impl Timetable {
                         } => {
                             // if it's a double block of the same length
                             if before_end + 1 + after_end == length {
-                                for j in 0..length {
-                                    let a = timetable.table(shift)[class_index].slots
-                                        [start_index + j as usize]
-                                        .clone();
-                                    let b = timetable.table(shift)[class_index].slots
-                                        [index as usize - before_end as usize + j as usize]
-                                        .clone();
-
-                                    timetable.table_mut(shift)[class_index].slots
-                                        [start_index + j as usize] = b;
-
-                                    timetable.table_mut(shift)[class_index].slots
-                                        [index as usize - before_end as usize + j as usize] = a;
+                                let slots = &mut self.table_mut(shift)[class_index].slots;
+                                let other_start = index as usize - before_end as usize;
+                                for j in 0..length as usize {
+                                    slots.swap(start_index + j, other_start + j);
                                 }
 
                                 done = true;
@@ -909,44 +1842,174 @@ impl Timetable {
 
         // println!("=================\n");
 
-        // send_timetable(&timetable, _out);
+        // send_timetable(&self, _out);
 
-        timetable
+        Move::between(shift, class_index, &before, &self.table(shift)[class_index])
     }
 
-    // Should be 0
-    pub fn hard_points(&self, shift: Shift, multiplier: i32) -> i32 {
-        let mut points = 0;
+    /// (class, relation, slot) of every lesson a move puts into a slot, or takes out of one without `after`
+    fn placements(&self, mv: &Move, after: bool) -> Vec<Placement> {
+        let class = self.table(mv.shift)[mv.class].class_index as usize;
+
+        let mut placements: Vec<Placement> = vec![];
+        for (index, before_slot, after_slot) in mv.changes.iter() {
+            let slot = if after { after_slot } else { before_slot };
+            let slot_datas = match *slot {
+                Slot::Single(s) => [s, SlotData::Empty],
+                Slot::Double { first, second, .. } => [first, second],
+            };
+
+            for slot_data in slot_datas {
+                if let SlotData::PartiallyFilled {
+                    teacher, subject, ..
+                } = slot_data
+                {
+                    let relation = self.data.relations.iter().position(|relation| {
+                        mv.shift.equals(relation.shift)
+                            && relation.class == class
+                            && relation.teacher == teacher
+                            && relation.subject == subject
+                    });
+
+                    if let Some(relation) = relation {
+                        placements.push((class, relation, *index));
+                    }
+                }
+            }
+        }
+
+        placements
+    }
+
+    /// Start index and length of the run of identical lessons around `index`, if they belong to a relation held in blocks
+    fn block_run(&self, shift: Shift, class_index: usize, index: usize) -> Option<(usize, usize)> {
+        let class_slots = &self.table(shift)[class_index];
+
+        let (teacher, subject) = match class_slots.slots[index] {
+            Slot::Single(SlotData::PartiallyFilled {
+                teacher, subject, ..
+            }) => (teacher, subject),
+            _ => return None,
+        };
+
+        let blocked = self.data.relations.iter().any(|relation| {
+            shift.equals(relation.shift)
+                && relation.class == class_slots.class_index as usize
+                && relation.teacher == teacher
+                && relation.subject == subject
+                && !relation.blocks.is_empty()
+        });
+        if !blocked {
+            return None;
+        }
 
-        points += multiplier * cost::hard_repeating_teachers(self, shift, false);
-        points += multiplier * cost::hard_holes_in_class_timetable(self, shift);
-        points += multiplier * cost::hard_too_many_subjects_of_same_kind(self, shift, false);
-        points += multiplier * cost::hard_block_classes(self, shift);
-        points += multiplier * cost::hard_specific_subject_days(self, shift);
-        points += multiplier * cost::hard_subject_per_day_limits(self, shift);
-        points += multiplier * cost::hard_subject_holes(self, shift, false);
-        points += multiplier * cost::hard_teacher_shift_spread(self, shift, false);
-        points += multiplier * cost::hard_teacher_extra_constraints(self, shift);
+        let same_lesson = |i: usize| match class_slots.slots[i] {
+            Slot::Single(SlotData::PartiallyFilled {
+                teacher: t,
+                subject: s,
+                ..
+            }) => t == teacher && s == subject,
+            _ => false,
+        };
+
+        let day_start = index - index % self.max_periods_per_day as usize;
+        let day_end = day_start + self.max_periods_per_day as usize;
 
-        points
+        let mut start = index;
+        while start > day_start && same_lesson(start - 1) {
+            start -= 1;
+        }
+
+        let mut end = index + 1;
+        while end < day_end && same_lesson(end) {
+            end += 1;
+        }
+
+        if end - start > 1 {
+            Some((start, end - start))
+        } else {
+            None
+        }
     }
 
-    // Should be as close to 0 as possible
-    pub fn soft_points(&self, shift: Shift, multiplier: i32) -> i32 {
-        let mut points = 0;
+    /// Swaps `length` single slots starting at `start` with the same number of single slots at a random place within a day
+    fn move_run(
+        &mut self,
+        shift: Shift,
+        class_index: usize,
+        start: usize,
+        length: usize,
+        rng: &mut impl Rng,
+    ) -> bool {
+        let max_periods_per_day = self.max_periods_per_day as usize;
+        if length > max_periods_per_day {
+            return false;
+        }
 
-        let teacher_table = util::class_table_to_teacher_table(
-            &self.table(shift),
-            &self.data,
-            self.max_periods_per_day,
-        );
+        // give up eventually, there might not be enough consecutive single slots anywhere
+        for _ in 0..100 {
+            let day = rng.gen_range(0..5);
+            let index =
+                day * max_periods_per_day + rng.gen_range(0..=(max_periods_per_day - length));
+
+            // overlapping with the run itself
+            if index < start + length && start < index + length {
+                continue;
+            }
+
+            let slots = &mut self.table_mut(shift)[class_index].slots;
+            if (0..length).all(|j| matches!(slots[index + j], Slot::Single(_))) {
+                for j in 0..length {
+                    slots.swap(start + j, index + j);
+                }
+
+                return true;
+            }
+        }
 
-        points += multiplier * cost::soft_class_spread(self, shift);
-        points += multiplier * cost::soft_teacher_class_spread(self, &teacher_table);
-        points += multiplier * cost::soft_holes_in_teacher_timetable(self, &teacher_table);
-        points += multiplier * cost::soft_preferred_subject_times(self, shift);
+        false
+    }
+
+    // Should be 0
+    pub fn hard_points(&self, shift: Shift, weights: &Weights) -> i32 {
+        self.constraints
+            .points(&ShiftView::new(self, shift), true, weights)
+    }
+
+    // Should be as close to 0 as possible
+    pub fn soft_points(&self, shift: Shift, weights: &Weights) -> i32 {
+        self.constraints
+            .points(&ShiftView::new(self, shift), false, weights)
+    }
+
+    /// Assigns a room to the lesson if a room rule matches it, marking the room as used
+    fn pin_room(&self, class: usize, slot_data: SlotData, used_rooms: &mut Vec<usize>) -> SlotData {
+        match slot_data {
+            SlotData::PartiallyFilled {
+                teacher, subject, ..
+            } => match self.data.room_rule(class, teacher, subject) {
+                Some(rule) => {
+                    let room = rule
+                        .rooms
+                        .iter()
+                        .find(|r| !used_rooms.contains(r))
+                        .or(rule.rooms.first())
+                        .copied();
+
+                    if let Some(r) = room {
+                        used_rooms.push(r);
+                    }
 
-        points
+                    SlotData::PartiallyFilled {
+                        teacher,
+                        subject,
+                        room,
+                    }
+                }
+                None => slot_data,
+            },
+            SlotData::Empty => slot_data,
+        }
     }
 
     pub fn fill_rooms(&mut self, shift: Shift) {
@@ -1019,226 +2082,40 @@ impl Timetable {
             }
         }
 
-        let mut timetable = self.table(shift).clone();
-
         let mut used_rooms: Vec<Vec<usize>> = Vec::new();
         used_rooms.resize(self.max_periods_per_day as usize * 5, vec![]);
-        let data = self.data.clone();
 
-        //
+        // rooms pinned by room rules take precedence over everything else
         for period in 0..(self.max_periods_per_day * 5) {
-            for class_slots in timetable.iter_mut() {
-                match &mut class_slots.slots[period as usize] {
-                    Slot::Single(SlotData::PartiallyFilled {
-                        room,
-                        subject,
-                        teacher,
-                    }) => {
-                        let mut r: Option<usize> = None;
-                        if data.teachers[*teacher].name == "Tasic Gordana" {
-                            if data.subjects[*subject].kind == "computer" {
-                                r = Some(23);
-                            }
-                        }
-                        if data.teachers[*teacher].name == "Dejan Maras" {
-                            r = Some(18);
-                        }
-
-                        if data.classes[class_slots.class_index as usize].name == "S4F" {
-                            let kind = data.subjects[*subject].kind.clone();
-                            r = if kind == "computer" || kind == "masinska-computer" {
-                                Some(29)
-                            } else if kind == "sala" {
-                                Some(7)
-                            } else {
-                                Some(15)
-                            };
-                        }
-
-                        if data.classes[class_slots.class_index as usize].name == "S2F" {
-                            let kind = data.subjects[*subject].kind.clone();
-                            r = if kind == "computer" || kind == "masinska-computer" {
-                                Some(28)
-                            } else if kind == "sala" {
-                                Some(6)
-                            } else {
-                                Some(14)
-                            };
-                        }
-
-                        if r.is_some() {
-                            used_rooms[period as usize].push(r.unwrap());
+            for c in 0..self.table(shift).len() {
+                let class = self.table(shift)[c].class_index as usize;
+                let used = &mut used_rooms[period as usize];
 
-                            self.table_mut(shift)[class_slots.class_index as usize].slots
-                                [period as usize] = Slot::Single(SlotData::PartiallyFilled {
-                                teacher: *teacher,
-                                subject: *subject,
-                                room: Some(r.unwrap()),
-                            });
-                        }
-                    }
+                let slot = match self.table(shift)[c].slots[period as usize] {
+                    Slot::Single(s) => Slot::Single(self.pin_room(class, s, used)),
                     Slot::Double {
                         first,
                         second,
                         before,
                         after,
-                    } => {
-                        match first {
-                            SlotData::PartiallyFilled {
-                                room,
-                                subject,
-                                teacher,
-                            } => {
-                                let mut r: Option<usize> = None;
-                                if data.teachers[*teacher].name == "Tasic Gordana" {
-                                    if data.subjects[*subject].kind == "computer" {
-                                        r = Some(23);
-                                    }
-                                }
-                                if data.teachers[*teacher].name == "Dejan Maras" {
-                                    r = Some(18);
-                                }
-
-                                if data.classes[class_slots.class_index as usize].name == "S4F" {
-                                    let kind = data.subjects[*subject].kind.clone();
-                                    r = if kind == "computer" || kind == "masinska-computer" {
-                                        Some(29)
-                                    } else if kind == "sala" {
-                                        Some(7)
-                                    } else {
-                                        Some(15)
-                                    };
-                                }
-
-                                if data.classes[class_slots.class_index as usize].name == "S2F" {
-                                    let kind = data.subjects[*subject].kind.clone();
-                                    r = if kind == "computer" || kind == "masinska-computer" {
-                                        Some(28)
-                                    } else if kind == "sala" {
-                                        Some(6)
-                                    } else {
-                                        Some(14)
-                                    };
-                                }
-
-                                if r.is_some() {
-                                    used_rooms[period as usize].push(r.unwrap());
-
-                                    self.table_mut(shift)[class_slots.class_index as usize].slots
-                                        [period as usize] = Slot::Double {
-                                        first: SlotData::PartiallyFilled {
-                                            teacher: *teacher,
-                                            subject: *subject,
-                                            room: Some(r.unwrap()),
-                                        },
-                                        second: match self.table(shift)
-                                            [class_slots.class_index as usize]
-                                            .slots[period as usize]
-                                        {
-                                            Slot::Double { second, .. } => second,
-                                            _ => unreachable!(),
-                                        },
-                                        before: *before,
-                                        after: *after,
-                                    };
-                                }
-                            }
-
-                            _ => {}
-                        }
-
-                        match second {
-                            SlotData::PartiallyFilled {
-                                room,
-                                subject,
-                                teacher,
-                            } => {
-                                let mut r: Option<usize> = None;
-                                if data.teachers[*teacher].name == "Tasic Gordana" {
-                                    if data.subjects[*subject].kind == "computer"
-                                        || data.subjects[*subject].kind == "masinska-computer"
-                                    {
-                                        r = Some(23);
-                                    }
-                                }
-                                if data.teachers[*teacher].name == "Dejan Maras" {
-                                    r = Some(18);
-                                }
-
-                                if data.classes[class_slots.class_index as usize].name == "S4F" {
-                                    let kind = data.subjects[*subject].kind.clone();
-                                    r = if kind == "computer" || kind == "masinska-computer" {
-                                        Some(29)
-                                    } else if kind == "sala" {
-                                        Some(7)
-                                    } else {
-                                        Some(15)
-                                    };
-                                }
-
-                                if data.classes[class_slots.class_index as usize].name == "S2F" {
-                                    let kind = data.subjects[*subject].kind.clone();
-                                    r = if kind == "computer" || kind == "masinska-computer" {
-                                        Some(28)
-                                    } else if kind == "sala" {
-                                        Some(6)
-                                    } else {
-                                        Some(14)
-                                    };
-                                }
-
-                                if r.is_some() {
-                                    used_rooms[period as usize].push(r.unwrap());
-
-                                    self.table_mut(shift)[class_slots.class_index as usize].slots
-                                        [period as usize] = Slot::Double {
-                                        first: match self.table(shift)
-                                            [class_slots.class_index as usize]
-                                            .slots[period as usize]
-                                        {
-                                            Slot::Double { first, .. } => first,
-                                            _ => unreachable!(),
-                                        },
-                                        second: SlotData::PartiallyFilled {
-                                            teacher: *teacher,
-                                            subject: *subject,
-                                            room: Some(r.unwrap()),
-                                        },
-                                        before: *before,
-                                        after: *after,
-                                    };
-                                }
-                            }
-
-                            _ => {}
-                        }
-                    }
+                    } => Slot::Double {
+                        first: self.pin_room(class, first, used),
+                        second: self.pin_room(class, second, used),
+                        before,
+                        after,
+                    },
+                };
 
-                    _ => {}
-                }
+                self.table_mut(shift)[c].slots[period as usize] = slot;
             }
         }
-        //
 
-        timetable = self.table(shift).clone();
+        let timetable = self.table(shift).clone();
+
+        let kinds = self.data.kinds_by_scarcity();
 
         for period in 0..(self.max_periods_per_day * 5) {
-            for kind in [
-                "masinska",
-                "14",
-                "sd",
-                "sala",
-                "14-23",
-                "regular",
-                "computer",
-                "computer-regular",
-                "masinska-14",
-                "masinska-sd",
-                "masinska-computer",
-                "masinska-regular",
-                "masinska-regular-sd",
-            ] {
-                let kind = kind.to_string();
+            for kind in kinds.iter() {
                 // println!("KIND: {}", kind);
 
                 let mut c: usize = 0;
@@ -1264,12 +2141,12 @@ impl Timetable {
                                 );
                                 */
 
-                                if self.data.subjects[subject].kind == kind {
+                                if self.data.subjects[subject].kind == *kind {
                                     let mut found = false;
                                     let mut i: usize = 0;
                                     for room in self.data.rooms.clone() {
                                         if !used_rooms[period as usize].contains(&i)
-                                            && room.kinds.contains(&kind)
+                                            && room.kinds.contains(kind)
                                         {
                                             //println!("    ROOM: {}", room.name);
                                             used_rooms[period as usize].push(i);
@@ -1324,12 +2201,12 @@ impl Timetable {
                                             .join(", "));
                                         */
 
-                                        if self.data.subjects[subject].kind == kind {
+                                        if self.data.subjects[subject].kind == *kind {
                                             let mut found = false;
                                             let mut i: usize = 0;
                                             for room in self.data.rooms.clone() {
                                                 if !used_rooms[period as usize].contains(&i)
-                                                    && room.kinds.contains(&kind)
+                                                    && room.kinds.contains(kind)
                                                 {
                                                     // println!("    ROOM: {}", room.name);
                                                     used_rooms[period as usize].push(i);
@@ -1393,12 +2270,12 @@ impl Timetable {
                                             .join(", "));
                                         */
 
-                                        if self.data.subjects[subject].kind == kind {
+                                        if self.data.subjects[subject].kind == *kind {
                                             let mut found = false;
                                             let mut i: usize = 0;
                                             for room in self.data.rooms.clone() {
                                                 if !used_rooms[period as usize].contains(&i)
-                                                    && room.kinds.contains(&kind)
+                                                    && room.kinds.contains(kind)
                                                 {
                                                     //println!("    ROOM: {}", room.name);
                                                     used_rooms[period as usize].push(i);
@@ -1456,3 +2333,439 @@ impl Timetable {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::files;
+
+    fn relation(
+        shift: i32,
+        class: usize,
+        teacher: usize,
+        subject: usize,
+        per_week: u32,
+    ) -> Relation {
+        Relation {
+            shift,
+            teacher,
+            subject,
+            class,
+            per_week_first: per_week,
+            per_week_second: None,
+            max_per_day: None,
+            blocks: vec![],
+        }
+    }
+
+    fn names(prefix: &str, n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("{}{}", prefix, i)).collect()
+    }
+
+    /// Three classes per shift with split and blocked relations, and every kind of data a constraint reads
+    fn test_timetable() -> Timetable {
+        let mut data = TimetableData {
+            classes: names("C", 6)
+                .into_iter()
+                .map(|name| Class { name })
+                .collect(),
+            rooms: names("R", 4)
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| Room {
+                    name,
+                    kinds: if i == 0 {
+                        vec![String::from("lab"), String::from("regular")]
+                    } else {
+                        vec![String::from("regular")]
+                    },
+                })
+                .collect(),
+            subjects: names("S", 5)
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| Subject {
+                    name,
+                    kind: String::from(if i == 4 { "lab" } else { "regular" }),
+                    max_per_day: Some(2),
+                })
+                .collect(),
+            teachers: names("T", 6)
+                .into_iter()
+                .map(|name| Teacher { name })
+                .collect(),
+            ..Default::default()
+        };
+
+        for class in 0..6 {
+            let shift = if class < 3 { 1 } else { 2 };
+            let teacher = |i: usize| (class + i) % 6;
+
+            data.relations.push(Relation {
+                blocks: vec![2, 1],
+                ..relation(shift, class, teacher(0), 0, 3)
+            });
+            data.relations.push(Relation {
+                max_per_day: Some(1),
+                ..relation(shift, class, teacher(1), 1, 4)
+            });
+            data.relations
+                .push(relation(shift, class, teacher(2), 2, 5));
+            data.relations
+                .push(relation(shift, class, teacher(3), 3, 4));
+            data.relations.push(Relation {
+                per_week_second: Some(2),
+                ..relation(shift, class, teacher(4), 4, 2)
+            });
+            data.relations.push(Relation {
+                per_week_second: Some(2),
+                ..relation(shift, class, teacher(5), 4, 2)
+            });
+        }
+
+        data.teacher_availability.push(TeacherAvailability {
+            teacher: 0,
+            days: [
+                DayAvailability::First,
+                DayAvailability::Both,
+                DayAvailability::Second,
+                DayAvailability::Off,
+                DayAvailability::Both,
+            ],
+        });
+        for (teacher, hard) in [(1, true), (2, false)] {
+            data.teacher_unavailability.push(TeacherUnavailability {
+                teacher,
+                day: 1,
+                first_period: 0,
+                last_period: 3,
+                hard,
+                weight: 2,
+            });
+        }
+        for (kind, hard) in [
+            (SubjectTimeKind::Forbidden, true),
+            (SubjectTimeKind::Allowed, false),
+            (SubjectTimeKind::NotLast, false),
+        ] {
+            data.subject_times.push(SubjectTime {
+                subject: 2,
+                kind,
+                periods: vec![0, 1, 2],
+                hard,
+                weight: 1,
+            });
+        }
+        data.subject_days.push(SubjectDays {
+            subjects: vec![0, 1],
+            classes: vec![0, 4],
+            kind: SubjectDaysKind::MinFreeDays,
+            days: 1,
+        });
+
+        let mut timetable = Timetable::new(data, 7, vec![], vec![]);
+        timetable.generate_random_table(Shift::First);
+        timetable.generate_random_table(Shift::Second);
+        // so that lessons have rooms to repeat
+        timetable.fill_rooms(Shift::First);
+        timetable.fill_rooms(Shift::Second);
+
+        timetable
+    }
+
+    /// Parameters of a short seeded run, with `overrides` applied to the play data
+    fn params(overrides: Value) -> AnnealingParams {
+        let mut data = json!({
+            "alpha": 0.9,
+            "t0": 5.0,
+            "sa_max": 20,
+            "static_classes": "",
+            "seed": 42,
+            "progress_ms": 0,
+        });
+        for (key, value) in overrides.as_object().unwrap() {
+            data[key] = value.clone();
+        }
+
+        AnnealingParams::from_json(&data)
+    }
+
+    fn weights() -> Weights {
+        Weights::new(3, 1, 2, 1)
+    }
+
+    fn solve(timetable: &mut Timetable, params: &AnnealingParams) -> AnnealingResult {
+        let running = AtomicBool::new(true);
+        timetable.start_algorithm(&running, params, &weights(), &mut NoEvents)
+    }
+
+    fn tables(timetable: &Timetable) -> Value {
+        json!([timetable.table1, timetable.table2])
+    }
+
+    fn random_shift(rng: &mut impl Rng) -> Shift {
+        if rng.gen_bool(0.5) {
+            Shift::First
+        } else {
+            Shift::Second
+        }
+    }
+
+    fn assert_matches_full_evaluation(solution: &ScoredSolution, weights: &Weights) {
+        let timetable = &solution.timetable;
+
+        for (shift, scores) in [
+            (Shift::First, &solution.scores1),
+            (Shift::Second, &solution.scores2),
+        ] {
+            let view = ShiftView::new(timetable, shift);
+
+            for (e, total) in timetable.constraints.entries.iter().zip(scores.totals()) {
+                if e.enabled {
+                    assert_eq!(
+                        *total,
+                        e.constraint.evaluate(&view, None),
+                        "{} in {:?}",
+                        e.constraint.id(),
+                        shift
+                    );
+                }
+            }
+        }
+
+        let report = timetable.detailed_cost(weights);
+        assert_eq!(
+            solution.cost,
+            CostBreakdown {
+                teacher_shifts: report.teacher_shifts.cost,
+                hard_1: report.shifts[0].hard,
+                soft_1: report.shifts[0].soft,
+                hard_2: report.shifts[1].hard,
+                soft_2: report.shifts[1].soft,
+            }
+        );
+    }
+
+    #[test]
+    fn incremental_scores_match_full_evaluation() {
+        let timetable = test_timetable();
+
+        let mut weights = Weights::new(3, 1, 2, 1);
+        weights.constraints.insert(String::from("holes"), 5);
+        weights
+            .constraints
+            .insert(String::from("teacher_shifts"), 2);
+
+        let static_classes = "";
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut solution = ScoredSolution::new(&timetable, &weights);
+
+        for i in 0..600 {
+            if i % 3 == 0 {
+                // a batch of neighbors of the same solution, one of which is kept, like tabu search does
+                let mut candidates: Vec<Candidate> = (0..4)
+                    .map(|_| {
+                        let shift = random_shift(&mut rng);
+                        let candidate = solution.propose(shift, &weights, static_classes, &mut rng);
+                        solution.withdraw(&candidate);
+
+                        candidate
+                    })
+                    .collect();
+
+                let kept = candidates.swap_remove(rng.gen_range(0..candidates.len()));
+                solution.keep_withdrawn(kept, &weights);
+            } else {
+                let candidate1 = solution.propose(Shift::First, &weights, static_classes, &mut rng);
+                let candidate2 =
+                    solution.propose(Shift::Second, &weights, static_classes, &mut rng);
+
+                solution.settle(
+                    vec![
+                        (candidate1, rng.gen_bool(0.5)),
+                        (candidate2, rng.gen_bool(0.5)),
+                    ],
+                    &weights,
+                );
+            }
+
+            if i % 50 == 0 {
+                assert_matches_full_evaluation(&solution, &weights);
+            }
+        }
+
+        assert_matches_full_evaluation(&solution, &weights);
+    }
+
+    #[test]
+    fn same_seed_gives_same_tables() {
+        let timetable = test_timetable();
+        let params = params(json!({ "max_iterations": 500 }));
+
+        let mut first = timetable.clone();
+        let mut second = timetable.clone();
+        let first_result = solve(&mut first, &params);
+        let second_result = solve(&mut second, &params);
+
+        assert_eq!(tables(&first), tables(&second));
+        assert_eq!(first_result.best, second_result.best);
+        assert_eq!(first.seed, Some(42));
+    }
+
+    #[test]
+    fn seed_round_trips_through_export() {
+        let mut timetable = test_timetable();
+        solve(&mut timetable, &params(json!({ "max_iterations": 100 })));
+
+        let path = std::env::temp_dir().join(format!("tomoyo-seed-{}.json", std::process::id()));
+        std::fs::write(&path, files::timetable_json(&timetable).to_string()).unwrap();
+
+        let mut imported = test_timetable();
+        assert_eq!(imported.seed, None);
+        let result = files::import_timetable(&mut imported, &path);
+        std::fs::remove_file(&path).unwrap();
+
+        result.unwrap();
+        assert_eq!(imported.seed, Some(42));
+        assert_eq!(tables(&imported), tables(&timetable));
+    }
+
+    #[test]
+    fn stops_at_max_iterations() {
+        let result = solve(
+            &mut test_timetable(),
+            &params(json!({ "max_iterations": 150 })),
+        );
+
+        assert_eq!(result.reason, StopReason::IterationLimit);
+        assert_eq!(result.iterations, 150);
+    }
+
+    #[test]
+    fn stops_at_min_temperature() {
+        // 5 * 0.9^n drops below 1 after 16 temperature steps
+        let result = solve(
+            &mut test_timetable(),
+            &params(json!({ "min_temperature": 1.0 })),
+        );
+
+        assert_eq!(result.reason, StopReason::MinTemperature);
+        assert_eq!(result.iterations, 16 * 20);
+    }
+
+    #[test]
+    fn stops_when_stagnating() {
+        // the iteration limit only keeps a broken criterion from hanging the test
+        let result = solve(
+            &mut test_timetable(),
+            &params(json!({ "stagnation_steps": 3, "max_iterations": 1_000_000 })),
+        );
+
+        assert_eq!(result.reason, StopReason::Stagnation);
+    }
+
+    #[test]
+    fn tabu_list_forbids_recent_placements() {
+        let mut tabu = TabuList::new(2);
+        tabu.push(vec![(0, 1, 2), (0, 3, 4)]);
+
+        assert!(tabu.forbids(&[(0, 3, 4)]));
+        assert!(tabu.forbids(&[(5, 5, 5), (0, 1, 2)]));
+        // every part of the placement has to match
+        assert!(!tabu.forbids(&[(1, 1, 2)]));
+        assert!(!tabu.forbids(&[(0, 2, 2)]));
+        assert!(!tabu.forbids(&[(0, 1, 3)]));
+        assert!(!tabu.forbids(&[]));
+    }
+
+    #[test]
+    fn tabu_list_expires_after_tenure() {
+        let mut tabu = TabuList::new(2);
+        tabu.push(vec![(0, 1, 2)]);
+        tabu.push(vec![(1, 1, 1)]);
+        assert!(tabu.forbids(&[(0, 1, 2)]));
+
+        tabu.push(vec![(2, 2, 2)]);
+        assert!(!tabu.forbids(&[(0, 1, 2)]));
+        assert!(tabu.forbids(&[(1, 1, 1)]));
+
+        let mut no_tenure = TabuList::new(0);
+        no_tenure.push(vec![(0, 1, 2)]);
+        assert!(!no_tenure.forbids(&[(0, 1, 2)]));
+    }
+
+    #[test]
+    fn tabu_move_beating_the_best_is_admitted() {
+        let mut tabu = TabuList::new(5);
+        tabu.push(vec![(0, 1, 2)]);
+
+        assert!(!tabu.admits(&[(0, 1, 2)], 10, 10));
+        assert!(!tabu.admits(&[(0, 1, 2)], 11, 10));
+        assert!(tabu.admits(&[(0, 1, 2)], 9, 10));
+        assert!(tabu.admits(&[(0, 1, 3)], 11, 10));
+    }
+
+    #[test]
+    fn tabu_search_keeps_best_no_worse_than_start() {
+        let mut timetable = test_timetable();
+        let start = ScoredSolution::new(&timetable, &weights()).cost.total();
+
+        let result = solve(
+            &mut timetable,
+            &params(json!({
+                "algorithm": "tabu",
+                "max_iterations": 100,
+                "tabu_neighbors": 10,
+                "tabu_tenure": 5,
+            })),
+        );
+
+        assert_eq!(result.reason, StopReason::IterationLimit);
+        assert!(result.best.total() <= start);
+        assert_eq!(
+            ScoredSolution::new(&timetable, &weights()).cost,
+            result.best
+        );
+    }
+
+    #[test]
+    fn parallel_run_reports_best_chain() {
+        // chains never share their best solution within the budget, so each one runs as it would alone
+        let params = params(json!({
+            "chains": 2,
+            "exchange_steps": 1_000_000,
+            "chain_temperature_ratio": 0.5,
+            "max_iterations": 300,
+        }));
+        let mut timetable = test_timetable();
+
+        let running = AtomicBool::new(true);
+        let chain_costs: Vec<CostBreakdown> = (0..2)
+            .map(|i| {
+                let chain = Chain {
+                    seed: params.seed + i,
+                    t0: params.t0 * 0.5_f64.powi(i as i32),
+                };
+                timetable
+                    .anneal_chain(&running, &params, chain, &weights(), &mut NoEvents, None)
+                    .best
+                    .cost
+            })
+            .collect();
+
+        let result = solve(&mut timetable, &params);
+
+        assert_eq!(result.reason, StopReason::IterationLimit);
+        assert_eq!(result.iterations, 300);
+        assert_eq!(
+            result.best.total(),
+            chain_costs.iter().map(|c| c.total()).min().unwrap()
+        );
+        assert_eq!(
+            ScoredSolution::new(&timetable, &weights()).cost,
+            result.best
+        );
+    }
+}
@@ -1,12 +1,9 @@
-use csv::{Reader, Writer};
-use serde::{Deserialize, Serialize};
+use csv::Writer;
 use serde_json::{json, Value};
 use std::{
     fs,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
-    },
+    path::Path,
+    sync::{atomic::AtomicBool, atomic::Ordering, Arc, Mutex},
     thread,
     time::Instant,
     vec,
@@ -14,25 +11,65 @@ use std::{
 use ws::{Builder, Handler, Handshake, Message, Sender, Settings};
 
 use project_tomoyo::*;
+use project_tomoyo::{
+    cost::{ConstraintRegistry, CostBreakdown, CostReport, Weights},
+    events::{LogEvents, Progress, SolverEvents},
+    files::{self, *},
+    logic::{
+        AnnealingParams, AnnealingResult, ClassSlots, Escape, Shift, StopReason, Timetable,
+        TimetableData,
+    },
+    regular_tabs::*,
+    util::*,
+};
 
-pub mod cost;
-pub mod regular_tabs;
-use regular_tabs::*;
+const MAX_PERIODS_PER_DAY: u32 = 7;
 
-use crate::logic::{Shift, Timetable, TimetableData};
+/// Sends the solver's results to the frontend, while logging everything like the CLI does
+struct WsEvents {
+    out: Sender,
+}
 
-pub mod logic;
+impl SolverEvents for WsEvents {
+    fn started(&mut self, params: &AnnealingParams) {
+        LogEvents.started(params);
+    }
 
-pub mod util;
-use util::*;
+    fn accepted(&mut self, temperature: f64, current: &CostBreakdown, best: &CostBreakdown) {
+        LogEvents.accepted(temperature, current, best);
+    }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct RoomRecord {
-    name: String,
-    kinds: String,
-}
+    fn escaped(&mut self, escape: Escape, temperature: f64, current: &CostBreakdown) {
+        LogEvents.escaped(escape, temperature, current);
+    }
 
-const MAX_PERIODS_PER_DAY: u32 = 7;
+    fn progress(&mut self, progress: &Progress) {
+        LogEvents.progress(progress);
+
+        send_progress(&self.out, progress);
+    }
+
+    fn best_snapshot(
+        &mut self,
+        table1: &[ClassSlots],
+        table2: &[ClassSlots],
+        cost: &CostBreakdown,
+    ) {
+        send_best_timetable(&self.out, MAX_PERIODS_PER_DAY, table1, table2, cost);
+    }
+
+    fn finished(&mut self, result: &AnnealingResult, timetable: &Timetable, report: &CostReport) {
+        LogEvents.finished(result, timetable, report);
+
+        send_finished(&self.out, result);
+        send_detailed_cost(&self.out, report);
+
+        // a pause sends the timetable on its own
+        if result.reason != StopReason::Paused {
+            send_timetable(timetable, &self.out);
+        }
+    }
+}
 
 struct Server {
     out: Sender,
@@ -75,9 +112,9 @@ impl Handler for Server {
                     "classes" => {
                         let data = parsed_msg["data"].as_array().unwrap();
 
-                        timetable.data.classes.clear();
+                        timetable.data_mut().classes.clear();
                         for v in data.iter() {
-                            timetable.data.classes.push(Class {
+                            timetable.data_mut().classes.push(Class {
                                 name: String::from(v.as_str().unwrap()),
                             })
                         }
@@ -85,11 +122,11 @@ impl Handler for Server {
                     "rooms" => {
                         let data = parsed_msg["data"].as_array().unwrap();
 
-                        timetable.data.rooms.clear();
+                        timetable.data_mut().rooms.clear();
                         for v in data.iter() {
                             let kinds: String = String::from(v["kinds"].as_str().unwrap());
 
-                            timetable.data.rooms.push(Room {
+                            timetable.data_mut().rooms.push(Room {
                                 name: String::from(v["name"].as_str().unwrap()),
                                 kinds: kinds.split(' ').map(str::to_string).collect(),
                             })
@@ -98,20 +135,21 @@ impl Handler for Server {
                     "subjects" => {
                         let data = parsed_msg["data"].as_array().unwrap();
 
-                        timetable.data.subjects.clear();
+                        timetable.data_mut().subjects.clear();
                         for v in data.iter() {
-                            timetable.data.subjects.push(Subject {
+                            timetable.data_mut().subjects.push(Subject {
                                 name: String::from(v["name"].as_str().unwrap()),
                                 kind: String::from(v["kind"].as_str().unwrap()),
+                                max_per_day: v["maxPerDay"].as_u64().map(|n| n as u32),
                             })
                         }
                     }
                     "teachers" => {
                         let data = parsed_msg["data"].as_array().unwrap();
 
-                        timetable.data.teachers.clear();
+                        timetable.data_mut().teachers.clear();
                         for v in data.iter() {
-                            timetable.data.teachers.push(Teacher {
+                            timetable.data_mut().teachers.push(Teacher {
                                 name: String::from(v.as_str().unwrap()),
                             })
                         }
@@ -119,7 +157,7 @@ impl Handler for Server {
                     "relations" => {
                         let data = parsed_msg["data"].as_array().unwrap();
 
-                        timetable.data.relations.clear();
+                        timetable.data_mut().relations.clear();
                         for v in data.iter() {
                             // annoying process to convert it to an Option<u32>
                             let mut second: Option<u32> = None;
@@ -130,23 +168,61 @@ impl Handler for Server {
                                 None => {}
                             }
 
-                            timetable.data.relations.push(Relation {
+                            timetable.data_mut().relations.push(Relation {
                                 shift: v["shift"].as_i64().unwrap() as i32,
                                 teacher: v["teacher"].as_u64().unwrap() as usize,
                                 subject: v["subject"].as_u64().unwrap() as usize,
                                 class: v["class_"].as_u64().unwrap() as usize,
                                 per_week_first: v["perWeekFirst"].as_u64().unwrap() as u32,
                                 per_week_second: second,
+                                max_per_day: v["maxPerDay"].as_u64().map(|n| n as u32),
+                                blocks: match v["blocks"].as_array() {
+                                    Some(blocks) => {
+                                        blocks.iter().map(|b| b.as_u64().unwrap() as u32).collect()
+                                    }
+                                    None => vec![],
+                                },
                             })
                         }
                     }
+                    "teacher_availability" => {
+                        update_teacher_availability(
+                            &mut timetable.data_mut().teacher_availability,
+                            &parsed_msg["data"],
+                        );
+                    }
+                    "teacher_unavailability" => {
+                        update_teacher_unavailability(
+                            &mut timetable.data_mut().teacher_unavailability,
+                            &parsed_msg["data"],
+                        );
+                    }
+                    "room_rules" => {
+                        update_room_rules(
+                            &mut timetable.data_mut().room_rules,
+                            &parsed_msg["data"],
+                        );
+                    }
+                    "subject_times" => {
+                        update_subject_times(
+                            &mut timetable.data_mut().subject_times,
+                            &parsed_msg["data"],
+                        );
+                    }
+                    "subject_days" => {
+                        update_subject_days(
+                            &mut timetable.data_mut().subject_days,
+                            &parsed_msg["data"],
+                        );
+                    }
                     _ => {}
                 }
             }
 
             Some("initial_timetable") => {
-                timetable.generate_random_table(Shift::First, &self.out);
-                timetable.generate_random_table(Shift::Second, &self.out);
+                timetable.generate_random_table(Shift::First);
+                timetable.generate_random_table(Shift::Second);
+                timetable.seed = None;
                 send_timetable(&timetable, &self.out);
             }
 
@@ -167,37 +243,28 @@ impl Handler for Server {
                     timetable.table2[i] = serde_json::from_value(table[i].clone()).unwrap();
                 }
 
+                timetable.constraints =
+                    Arc::new(ConstraintRegistry::from_json(&parsed_msg["data"]));
+
                 self.running_algorithm
                     .store(true, std::sync::atomic::Ordering::Relaxed);
                 let timetable_local_ref = self.timetable.clone(); // cloned reference to timetable
                 let running_algorithm_local_ref = self.running_algorithm.clone(); // cloned reference to timetable
                 let out_local_ref = self.out.clone(); // cloned reference to out channel
 
-                let alpha = parsed_msg["data"]["alpha"].as_f64().unwrap();
-                let t0 = parsed_msg["data"]["t0"].as_f64().unwrap();
-                let sa_max = parsed_msg["data"]["sa_max"].as_i64().unwrap();
-                let static_classes = parsed_msg["data"]["static_classes"]
-                    .as_str()
-                    .unwrap()
-                    .to_string();
-                let hard_1 = parsed_msg["data"]["hard_1"].as_i64().unwrap() as i32;
-                let soft_1 = parsed_msg["data"]["soft_1"].as_i64().unwrap() as i32;
-                let hard_2 = parsed_msg["data"]["hard_2"].as_i64().unwrap() as i32;
-                let soft_2 = parsed_msg["data"]["soft_2"].as_i64().unwrap() as i32;
+                let params = AnnealingParams::from_json(&parsed_msg["data"]);
+                let weights = Weights::from_json(&parsed_msg["data"], &timetable.constraints);
 
                 thread::spawn(move || {
                     timetable_local_ref.lock().unwrap().start_algorithm(
-                        running_algorithm_local_ref,
-                        &out_local_ref,
-                        alpha,
-                        t0,
-                        sa_max,
-                        &static_classes,
-                        hard_1,
-                        soft_1,
-                        hard_2,
-                        soft_2,
+                        &running_algorithm_local_ref,
+                        &params,
+                        &weights,
+                        &mut WsEvents { out: out_local_ref },
                     );
+
+                    // it might have stopped on its own
+                    running_algorithm_local_ref.store(false, Ordering::Relaxed);
                 });
             }
 
@@ -225,12 +292,12 @@ impl Handler for Server {
                     timetable.table2[i] = serde_json::from_value(data[i].clone()).unwrap();
                 }
 
-                let hard_1 = parsed_msg["data"]["hard_1"].as_i64().unwrap() as i32;
-                let soft_1 = parsed_msg["data"]["soft_1"].as_i64().unwrap() as i32;
-                let hard_2 = parsed_msg["data"]["hard_2"].as_i64().unwrap() as i32;
-                let soft_2 = parsed_msg["data"]["soft_2"].as_i64().unwrap() as i32;
+                timetable.constraints =
+                    Arc::new(ConstraintRegistry::from_json(&parsed_msg["data"]));
+                let weights = Weights::from_json(&parsed_msg["data"], &timetable.constraints);
 
-                timetable.detailed_cost(hard_1, soft_1, hard_2, soft_2);
+                let report = timetable.detailed_cost(&weights);
+                send_detailed_cost(&self.out, &report);
             }
 
             _ => panic!("Invalid message."),
@@ -245,65 +312,36 @@ fn handle_import(timetable: &mut Timetable, parsed_msg: &Value, out: &Sender) {
 
     match tab {
         "timetable" => {
-            let contents = fs::read_to_string("./import/timetable.json").unwrap();
-            let json: Value = serde_json::from_str(&contents).unwrap();
-
-            let table1 = json["table1"].as_array().unwrap();
-            for i in 0..timetable.table1.len() {
-                timetable.table1[i] = serde_json::from_value(table1[i].clone()).unwrap();
-            }
-
-            let table2 = json["table2"].as_array().unwrap();
-            for i in 0..timetable.table2.len() {
-                timetable.table2[i] = serde_json::from_value(table2[i].clone()).unwrap();
+            if let Err(message) =
+                files::import_timetable(timetable, Path::new("./import/timetable.json"))
+            {
+                println!("Failed to import timetable: {}", message);
+                return;
             }
 
             send_timetable(timetable, out);
         }
         _ => {
-            let mut rdr = Reader::from_path(format!("./import/{}.csv", tab.clone())).unwrap();
+            if let Err(message) = files::import_tab(timetable, Path::new("./import"), tab) {
+                println!("Failed to import {}: {}", tab, message);
+                return;
+            }
 
             match tab {
-                "classes" => {
-                    for result in rdr.deserialize() {
-                        let record: Class = result.unwrap();
-                        timetable.data.classes.push(record);
-                    }
-                    send_classes(&out, &timetable.data.classes);
-                }
-                "rooms" => {
-                    for result in rdr.deserialize() {
-                        let record: RoomRecord = result.unwrap();
-
-                        let room: Room = Room {
-                            name: record.name,
-                            kinds: record.kinds.split(' ').map(str::to_string).collect(),
-                        };
-                        timetable.data.rooms.push(room);
-                    }
-                    send_rooms(&out, &timetable.data.rooms);
-                }
-                "subjects" => {
-                    for result in rdr.deserialize() {
-                        let record: Subject = result.unwrap();
-                        timetable.data.subjects.push(record);
-                    }
-                    send_subjects(&out, &timetable.data.subjects);
-                }
-                "teachers" => {
-                    for result in rdr.deserialize() {
-                        let record: Teacher = result.unwrap();
-                        timetable.data.teachers.push(record);
-                    }
-                    send_teachers(&out, &timetable.data.teachers);
+                "classes" => send_classes(out, &timetable.data.classes),
+                "rooms" => send_rooms(out, &timetable.data.rooms),
+                "subjects" => send_subjects(out, &timetable.data.subjects),
+                "teachers" => send_teachers(out, &timetable.data.teachers),
+                "relations" => send_relations(out, &timetable.data.relations),
+                "teacher_availability" => {
+                    send_teacher_availability(out, &timetable.data.teacher_availability)
                 }
-                "relations" => {
-                    for result in rdr.deserialize() {
-                        let record: Relation = result.unwrap();
-                        timetable.data.relations.push(record);
-                    }
-                    send_relations(&out, &timetable.data.relations);
+                "teacher_unavailability" => {
+                    send_teacher_unavailability(out, &timetable.data.teacher_unavailability)
                 }
+                "room_rules" => send_room_rules(out, &timetable.data.room_rules),
+                "subject_times" => send_subject_times(out, &timetable.data.subject_times),
+                "subject_days" => send_subject_days(out, &timetable.data.subject_days),
 
                 _ => {}
             }
@@ -316,10 +354,7 @@ fn handle_export(timetable: &mut Timetable, parsed_msg: &Value) {
 
     match tab {
         "timetable" => {
-            let json = json!({
-                "table1": timetable.table1,
-                "table2": timetable.table2,
-            });
+            let json = files::timetable_json(timetable);
             fs::write("./export/timetable.json", json.to_string()).unwrap();
         }
         _ => {
@@ -327,13 +362,13 @@ fn handle_export(timetable: &mut Timetable, parsed_msg: &Value) {
 
             match tab {
                 "classes" => {
-                    update_classes(&mut timetable.data.classes, &parsed_msg["data"]);
+                    update_classes(&mut timetable.data_mut().classes, &parsed_msg["data"]);
                     for row in timetable.data.classes.iter() {
                         wrtr.serialize(row).unwrap();
                     }
                 }
                 "rooms" => {
-                    update_rooms(&mut timetable.data.rooms, &parsed_msg["data"]);
+                    update_rooms(&mut timetable.data_mut().rooms, &parsed_msg["data"]);
                     for row in timetable.data.rooms.iter() {
                         let room_record = RoomRecord {
                             name: row.name.clone(),
@@ -343,23 +378,124 @@ fn handle_export(timetable: &mut Timetable, parsed_msg: &Value) {
                     }
                 }
                 "subjects" => {
-                    update_subjects(&mut timetable.data.subjects, &parsed_msg["data"]);
+                    update_subjects(&mut timetable.data_mut().subjects, &parsed_msg["data"]);
                     for row in timetable.data.subjects.iter() {
                         wrtr.serialize(row).unwrap();
                     }
                 }
                 "teachers" => {
-                    update_teachers(&mut timetable.data.teachers, &parsed_msg["data"]);
+                    update_teachers(&mut timetable.data_mut().teachers, &parsed_msg["data"]);
                     for row in timetable.data.teachers.iter() {
                         wrtr.serialize(row).unwrap();
                     }
                 }
                 "relations" => {
-                    update_relations(&mut timetable.data.relations, &parsed_msg["data"]);
+                    update_relations(&mut timetable.data_mut().relations, &parsed_msg["data"]);
                     for row in timetable.data.relations.iter() {
                         wrtr.serialize(row).unwrap();
                     }
                 }
+                "teacher_availability" => {
+                    update_teacher_availability(
+                        &mut timetable.data_mut().teacher_availability,
+                        &parsed_msg["data"],
+                    );
+                    for row in timetable.data.teacher_availability.iter() {
+                        let record = TeacherAvailabilityRecord {
+                            teacher: timetable.data.teachers[row.teacher].name.clone(),
+                            mon: row.days[0],
+                            tue: row.days[1],
+                            wed: row.days[2],
+                            thu: row.days[3],
+                            fri: row.days[4],
+                        };
+                        wrtr.serialize(record).unwrap();
+                    }
+                }
+                "teacher_unavailability" => {
+                    update_teacher_unavailability(
+                        &mut timetable.data_mut().teacher_unavailability,
+                        &parsed_msg["data"],
+                    );
+                    for row in timetable.data.teacher_unavailability.iter() {
+                        let record = TeacherUnavailabilityRecord {
+                            teacher: timetable.data.teachers[row.teacher].name.clone(),
+                            day: row.day,
+                            first_period: row.first_period,
+                            last_period: row.last_period,
+                            hard: row.hard,
+                            weight: row.weight,
+                        };
+                        wrtr.serialize(record).unwrap();
+                    }
+                }
+                "room_rules" => {
+                    update_room_rules(&mut timetable.data_mut().room_rules, &parsed_msg["data"]);
+                    for row in timetable.data.room_rules.iter() {
+                        let mut rooms: Vec<&str> = vec![];
+                        for room in row.rooms.iter() {
+                            let name = timetable.data.rooms[*room].name.as_str();
+                            if !rooms.contains(&name) {
+                                rooms.push(name);
+                            }
+                        }
+
+                        let record = RoomRuleRecord {
+                            teacher: row.teacher.map(|t| timetable.data.teachers[t].name.clone()),
+                            class: row.class.map(|c| timetable.data.classes[c].name.clone()),
+                            kinds: row.kinds.join(" "),
+                            rooms: rooms.join(" "),
+                            priority: row.priority,
+                        };
+                        wrtr.serialize(record).unwrap();
+                    }
+                }
+                "subject_times" => {
+                    update_subject_times(
+                        &mut timetable.data_mut().subject_times,
+                        &parsed_msg["data"],
+                    );
+                    for row in timetable.data.subject_times.iter() {
+                        let record = SubjectTimeRecord {
+                            subject: timetable.data.subjects[row.subject].name.clone(),
+                            kind: row.kind,
+                            periods: row
+                                .periods
+                                .iter()
+                                .map(|p| p.to_string())
+                                .collect::<Vec<String>>()
+                                .join(" "),
+                            hard: row.hard,
+                            weight: row.weight,
+                        };
+                        wrtr.serialize(record).unwrap();
+                    }
+                }
+                "subject_days" => {
+                    update_subject_days(
+                        &mut timetable.data_mut().subject_days,
+                        &parsed_msg["data"],
+                    );
+                    for row in timetable.data.subject_days.iter() {
+                        let record = SubjectDaysRecord {
+                            subjects: row
+                                .subjects
+                                .iter()
+                                .map(|s| timetable.data.subjects[*s].name.as_str())
+                                .collect::<Vec<&str>>()
+                                .join(";"),
+                            classes: row
+                                .classes
+                                .iter()
+                                .map(|c| timetable.data.classes[*c].name.as_str())
+                                .collect::<Vec<&str>>()
+                                .join(";"),
+                            kind: row.kind,
+                            days: row.days,
+                        };
+                        wrtr.serialize(record).unwrap();
+                    }
+                }
 
                 _ => {}
             }
@@ -394,13 +530,7 @@ fn main() {
         .build(|out| Server {
             out,
             timetable: Arc::new(Mutex::new(Timetable::new(
-                TimetableData {
-                    classes: vec![],
-                    rooms: vec![],
-                    subjects: vec![],
-                    teachers: vec![],
-                    relations: vec![],
-                },
+                TimetableData::default(),
                 MAX_PERIODS_PER_DAY,
                 vec![],
                 vec![],
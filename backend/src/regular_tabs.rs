@@ -1,8 +1,8 @@
-use project_tomoyo::*;
 use serde_json::{json, Value};
 use ws::Sender;
 
 use crate::util::*;
+use crate::*;
 
 pub fn send_classes(sender: &Sender, classes: &Vec<Class>) {
     let mut data: Vec<&str> = vec![];
@@ -41,7 +41,7 @@ pub fn send_subjects(sender: &Sender, subjects: &Vec<Subject>) {
     data.reserve(subjects.len());
 
     for subject in subjects.iter() {
-        data.push(json!({"name": subject.name.as_str(), "kind": subject.kind.as_str()}));
+        data.push(json!({"name": subject.name.as_str(), "kind": subject.kind.as_str(), "maxPerDay": subject.max_per_day}));
     }
 
     let json = json!({
@@ -73,7 +73,7 @@ pub fn send_relations(sender: &Sender, relations: &Vec<Relation>) {
     data.reserve(relations.len());
 
     for relation in relations.iter() {
-        data.push(json!({"shift": relation.shift, "teacher": relation.teacher, "subject": relation.subject, "class_": relation.class, "perWeekFirst": relation.per_week_first, "perWeekSecond": relation.per_week_second}));
+        data.push(json!({"shift": relation.shift, "teacher": relation.teacher, "subject": relation.subject, "class_": relation.class, "perWeekFirst": relation.per_week_first, "perWeekSecond": relation.per_week_second, "maxPerDay": relation.max_per_day, "blocks": relation.blocks}));
     }
 
     let json = json!({
@@ -84,6 +84,84 @@ pub fn send_relations(sender: &Sender, relations: &Vec<Relation>) {
 
     ws_send(&sender, &json);
 }
+pub fn send_teacher_availability(sender: &Sender, teacher_availability: &[TeacherAvailability]) {
+    let mut data: Vec<Value> = Vec::with_capacity(teacher_availability.len());
+
+    for availability in teacher_availability.iter() {
+        data.push(json!({"teacher": availability.teacher, "days": availability.days}));
+    }
+
+    let json = json!({
+        "kind": "list",
+        "tab": "teacher_availability",
+        "data": data
+    });
+
+    ws_send(sender, &json);
+}
+pub fn send_teacher_unavailability(
+    sender: &Sender,
+    teacher_unavailability: &[TeacherUnavailability],
+) {
+    let mut data: Vec<Value> = Vec::with_capacity(teacher_unavailability.len());
+
+    for unavailability in teacher_unavailability.iter() {
+        data.push(json!({"teacher": unavailability.teacher, "day": unavailability.day, "firstPeriod": unavailability.first_period, "lastPeriod": unavailability.last_period, "hard": unavailability.hard, "weight": unavailability.weight}));
+    }
+
+    let json = json!({
+        "kind": "list",
+        "tab": "teacher_unavailability",
+        "data": data
+    });
+
+    ws_send(sender, &json);
+}
+pub fn send_room_rules(sender: &Sender, room_rules: &[RoomRule]) {
+    let mut data: Vec<Value> = Vec::with_capacity(room_rules.len());
+
+    for rule in room_rules.iter() {
+        data.push(json!({"teacher": rule.teacher, "class_": rule.class, "kinds": rule.kinds.join(" "), "rooms": rule.rooms, "priority": rule.priority}));
+    }
+
+    let json = json!({
+        "kind": "list",
+        "tab": "room_rules",
+        "data": data
+    });
+
+    ws_send(sender, &json);
+}
+pub fn send_subject_times(sender: &Sender, subject_times: &[SubjectTime]) {
+    let mut data: Vec<Value> = Vec::with_capacity(subject_times.len());
+
+    for time in subject_times.iter() {
+        data.push(json!({"subject": time.subject, "kind": time.kind, "periods": time.periods, "hard": time.hard, "weight": time.weight}));
+    }
+
+    let json = json!({
+        "kind": "list",
+        "tab": "subject_times",
+        "data": data
+    });
+
+    ws_send(sender, &json);
+}
+pub fn send_subject_days(sender: &Sender, subject_days: &[SubjectDays]) {
+    let mut data: Vec<Value> = Vec::with_capacity(subject_days.len());
+
+    for constraint in subject_days.iter() {
+        data.push(json!({"subjects": constraint.subjects, "classes": constraint.classes, "kind": constraint.kind, "days": constraint.days}));
+    }
+
+    let json = json!({
+        "kind": "list",
+        "tab": "subject_days",
+        "data": data
+    });
+
+    ws_send(sender, &json);
+}
 
 pub fn update_classes(classes: &mut Vec<Class>, data: &Value) {
     match data.as_array() {
@@ -128,6 +206,7 @@ pub fn update_subjects(subjects: &mut Vec<Subject>, data: &Value) {
                 subjects.push(Subject {
                     name: String::from(el["name"].as_str().unwrap()),
                     kind: String::from(el["kind"].as_str().unwrap()),
+                    max_per_day: el["maxPerDay"].as_u64().map(|n| n as u32),
                 })
             }
         }
@@ -158,11 +237,8 @@ pub fn update_relations(relations: &mut Vec<Relation>, data: &Value) {
             for el in arr {
                 // annoying process to convert it to an Option<u32>
                 let mut second: Option<u32> = None;
-                match el["perWeekSecond"].as_u64() {
-                    Some(n) => {
-                        second = Some(n as u32);
-                    }
-                    None => {}
+                if let Some(n) = el["perWeekSecond"].as_u64() {
+                    second = Some(n as u32);
                 }
 
                 relations.push(Relation {
@@ -172,6 +248,131 @@ pub fn update_relations(relations: &mut Vec<Relation>, data: &Value) {
                     class: el["class_"].as_i64().unwrap() as usize,
                     per_week_first: el["perWeekFirst"].as_i64().unwrap() as u32,
                     per_week_second: second,
+                    max_per_day: el["maxPerDay"].as_u64().map(|n| n as u32),
+                    blocks: match el["blocks"].as_array() {
+                        Some(blocks) => blocks.iter().map(|b| b.as_u64().unwrap() as u32).collect(),
+                        None => vec![],
+                    },
+                })
+            }
+        }
+        None => panic!("Invalid message"),
+    }
+}
+pub fn update_teacher_availability(
+    teacher_availability: &mut Vec<TeacherAvailability>,
+    data: &Value,
+) {
+    match data.as_array() {
+        Some(arr) => {
+            teacher_availability.clear();
+            teacher_availability.reserve(arr.len());
+
+            for el in arr {
+                teacher_availability.push(TeacherAvailability {
+                    teacher: el["teacher"].as_u64().unwrap() as usize,
+                    days: serde_json::from_value(el["days"].clone()).unwrap(),
+                })
+            }
+        }
+        None => panic!("Invalid message"),
+    }
+}
+pub fn update_teacher_unavailability(
+    teacher_unavailability: &mut Vec<TeacherUnavailability>,
+    data: &Value,
+) {
+    match data.as_array() {
+        Some(arr) => {
+            teacher_unavailability.clear();
+            teacher_unavailability.reserve(arr.len());
+
+            for el in arr {
+                teacher_unavailability.push(TeacherUnavailability {
+                    teacher: el["teacher"].as_u64().unwrap() as usize,
+                    day: el["day"].as_u64().unwrap() as u32,
+                    first_period: el["firstPeriod"].as_u64().unwrap() as u32,
+                    last_period: el["lastPeriod"].as_u64().unwrap() as u32,
+                    hard: el["hard"].as_bool().unwrap(),
+                    weight: el["weight"].as_i64().unwrap() as i32,
+                })
+            }
+        }
+        None => panic!("Invalid message"),
+    }
+}
+pub fn update_room_rules(room_rules: &mut Vec<RoomRule>, data: &Value) {
+    match data.as_array() {
+        Some(arr) => {
+            room_rules.clear();
+            room_rules.reserve(arr.len());
+
+            for el in arr {
+                let kinds: String = String::from(el["kinds"].as_str().unwrap());
+
+                room_rules.push(RoomRule {
+                    teacher: el["teacher"].as_u64().map(|t| t as usize),
+                    class: el["class_"].as_u64().map(|c| c as usize),
+                    kinds: kinds.split_whitespace().map(str::to_string).collect(),
+                    rooms: el["rooms"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|r| r.as_u64().unwrap() as usize)
+                        .collect(),
+                    priority: el["priority"].as_i64().unwrap() as i32,
+                })
+            }
+        }
+        None => panic!("Invalid message"),
+    }
+}
+pub fn update_subject_times(subject_times: &mut Vec<SubjectTime>, data: &Value) {
+    match data.as_array() {
+        Some(arr) => {
+            subject_times.clear();
+            subject_times.reserve(arr.len());
+
+            for el in arr {
+                subject_times.push(SubjectTime {
+                    subject: el["subject"].as_u64().unwrap() as usize,
+                    kind: serde_json::from_value(el["kind"].clone()).unwrap(),
+                    periods: el["periods"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|p| p.as_u64().unwrap() as u32)
+                        .collect(),
+                    hard: el["hard"].as_bool().unwrap(),
+                    weight: el["weight"].as_i64().unwrap() as i32,
+                })
+            }
+        }
+        None => panic!("Invalid message"),
+    }
+}
+pub fn update_subject_days(subject_days: &mut Vec<SubjectDays>, data: &Value) {
+    match data.as_array() {
+        Some(arr) => {
+            subject_days.clear();
+            subject_days.reserve(arr.len());
+
+            for el in arr {
+                subject_days.push(SubjectDays {
+                    subjects: el["subjects"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|s| s.as_u64().unwrap() as usize)
+                        .collect(),
+                    classes: el["classes"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|c| c.as_u64().unwrap() as usize)
+                        .collect(),
+                    kind: serde_json::from_value(el["kind"].clone()).unwrap(),
+                    days: el["days"].as_u64().unwrap() as u32,
                 })
             }
         }
@@ -1,11 +1,15 @@
 use std::{collections::HashMap, println, vec};
 
-use serde_json::Value;
+use serde_json::{json, Value};
 use ws::Sender;
 
 use serde::{Deserialize, Serialize};
 
-use crate::logic::{ClassSlots, Timetable, TimetableData};
+use crate::{
+    cost::{CostBreakdown, CostReport},
+    events::Progress,
+    logic::{AnnealingResult, ClassSlots, Shift, Slot, SlotData, Timetable, TimetableData},
+};
 
 pub fn ws_send(sender: &Sender, json: &Value) {
     sender.send(json.to_string()).unwrap();
@@ -15,6 +19,89 @@ pub fn ws_send(sender: &Sender, json: &Value) {
     // println!("=====  MESSAGE SENT BY SERVER END  =====");
 }
 
+pub fn send_detailed_cost(sender: &Sender, report: &CostReport) {
+    let json = json!({
+        "kind": "detailed_cost",
+        "tab": "timetable",
+        "data": report
+    });
+
+    ws_send(sender, &json);
+}
+
+/// Why and how a run ended, the kept solution is the best one
+pub fn send_finished(sender: &Sender, result: &AnnealingResult) {
+    let json = json!({
+        "kind": "finished",
+        "tab": "timetable",
+        "data": {
+            "reason": result.reason,
+            "seed": result.seed,
+            "iterations": result.iterations,
+            "escapes": result.escapes,
+            "elapsed_secs": result.elapsed_secs,
+            "current": cost_json(&result.current),
+            "best": cost_json(&result.best),
+        }
+    });
+
+    ws_send(sender, &json);
+}
+
+/// Breakdown along with the cost each shift is annealed on and the total
+fn cost_json(cost: &CostBreakdown) -> Value {
+    json!({
+        "teacher_shifts": cost.teacher_shifts,
+        "hard_1": cost.hard_1,
+        "soft_1": cost.soft_1,
+        "hard_2": cost.hard_2,
+        "soft_2": cost.soft_2,
+        "shift_1": cost.shift(Shift::First),
+        "shift_2": cost.shift(Shift::Second),
+        "total": cost.total(),
+    })
+}
+
+pub fn send_progress(sender: &Sender, progress: &Progress) {
+    let json = json!({
+        "kind": "progress",
+        "tab": "timetable",
+        "data": {
+            "seed": progress.seed,
+            "temperature": progress.temperature,
+            "iteration": progress.iteration,
+            "current": cost_json(&progress.current),
+            "best": cost_json(&progress.best),
+            "acceptance_rate": progress.acceptance_rate,
+            "elapsed_secs": progress.elapsed_secs,
+        }
+    });
+
+    ws_send(sender, &json);
+}
+
+/// Best solution so far, in the same shape as a timetable message so the frontend could show it as is
+pub fn send_best_timetable(
+    sender: &Sender,
+    max_periods_per_day: u32,
+    table1: &[ClassSlots],
+    table2: &[ClassSlots],
+    cost: &CostBreakdown,
+) {
+    let json = json!({
+        "kind": "best_timetable",
+        "tab": "timetable",
+        "data": {
+            "max_periods_per_day": max_periods_per_day,
+            "table1": table1,
+            "table2": table2,
+            "cost": cost_json(cost),
+        }
+    });
+
+    ws_send(sender, &json);
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum TeacherSlot {
     Empty,
@@ -27,7 +114,7 @@ pub struct TeacherSlots {
 }
 
 pub fn class_table_to_teacher_table(
-    class_table: &Vec<ClassSlots>,
+    class_table: &[ClassSlots],
     table_data: &TimetableData,
     max_periods_per_day: u32,
 ) -> Vec<TeacherSlots> {
@@ -45,8 +132,7 @@ pub fn class_table_to_teacher_table(
     );
 
     for class_slots in class_table.iter() {
-        let mut i = 0;
-        for slot in class_slots.slots.iter() {
+        for (i, slot) in class_slots.slots.iter().enumerate() {
             match slot {
                 crate::logic::Slot::Single(s) => match s {
                     crate::logic::SlotData::Empty => {}
@@ -86,14 +172,48 @@ pub fn class_table_to_teacher_table(
                     }
                 }
             }
-
-            i += 1;
         }
     }
 
     table
 }
 
+/// Same as `class_table_to_teacher_table` except only for a single teacher
+pub fn teacher_slots(
+    class_table: &[ClassSlots],
+    teacher: usize,
+    max_periods_per_day: u32,
+) -> TeacherSlots {
+    let mut slots = vec![TeacherSlot::Empty; 5 * max_periods_per_day as usize];
+
+    for class_slots in class_table.iter() {
+        for (i, slot) in class_slots.slots.iter().enumerate() {
+            let slot_datas = match *slot {
+                Slot::Single(s) => [s, SlotData::Empty],
+                Slot::Double { first, second, .. } => [first, second],
+            };
+
+            for slot_data in slot_datas {
+                if let SlotData::PartiallyFilled {
+                    teacher: t,
+                    subject,
+                    ..
+                } = slot_data
+                {
+                    if t == teacher {
+                        slots[i] = TeacherSlot::PartiallyFilled {
+                            class: class_slots.class_index as usize,
+                            subject,
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    TeacherSlots { slots }
+}
+
 pub fn teacher_count_per_shift(timetable: &Timetable) {
     let mut count1: HashMap<usize, u32> = HashMap::new();
     let mut count2: HashMap<usize, u32> = HashMap::new();